@@ -0,0 +1,275 @@
+use std::{
+    cmp::Ordering,
+    fs,
+    path::Path,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+
+use crate::{
+    HTTPResponse, RequestURL,
+    defaults::NOINDEX_FILE,
+    resolve::Resolver,
+    status::{ClientErrorCode, HTTPStatusCode, ServerErrorCode},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Name,
+    Size,
+    Modified,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Entry {
+    pub name: String,
+    pub directory: bool,
+    pub size: Option<u64>,
+    // -> Seconds since the Unix epoch
+    pub modified: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct Listing<'a> {
+    path: &'a str,
+    entries: &'a [Entry],
+}
+
+// -> Lists a directory that has no index file, as HTML or as JSON if the client prefers it
+pub fn render(
+    resolver: &Resolver,
+    dir: &Path,
+    url: &RequestURL,
+    accept: Option<&str>,
+) -> Result<HTTPResponse, HTTPStatusCode> {
+    // -> A .noindex file opts the directory out, it looks like there is no listing at all
+    if dir.join(NOINDEX_FILE).exists() {
+        return Err(HTTPStatusCode::ClientError(ClientErrorCode::NotFound));
+    }
+
+    let sort = match url.param("sort") {
+        Some("size") => SortKey::Size,
+        Some("modified") => SortKey::Modified,
+        _ => SortKey::Name,
+    };
+    let descending = url.param("order") == Some("desc");
+
+    let mut entries = read_entries(resolver, dir)?;
+    sort_entries(&mut entries, sort, descending);
+
+    let base = format!("/{}", dir_path(url.segments()));
+
+    if prefers_json(accept) {
+        let listing = Listing {
+            path: &base,
+            entries: &entries,
+        };
+
+        return match serde_json::to_string(&listing) {
+            Ok(json) => Ok(HTTPResponse::builder()
+                .header("Content-Type", "application/json")
+                .header("Vary", "Accept")
+                .body(json)
+                .build()),
+            Err(_) => Err(HTTPStatusCode::ServerError(
+                ServerErrorCode::InternalServerError,
+            )),
+        };
+    }
+
+    Ok(HTTPResponse::builder()
+        .header("Content-Type", "text/html; charset=utf-8")
+        .header("Vary", "Accept")
+        .body(html(url.segments(), &entries, sort, descending))
+        .build())
+}
+
+fn read_entries(resolver: &Resolver, dir: &Path) -> Result<Vec<Entry>, HTTPStatusCode> {
+    let read = match fs::read_dir(dir) {
+        Ok(r) => r,
+        Err(_) => return Err(HTTPStatusCode::ClientError(ClientErrorCode::Forbidden)),
+    };
+
+    let mut entries = Vec::new();
+
+    for item in read.flatten() {
+        let name = match item.file_name().into_string() {
+            Ok(n) => n,
+            Err(_) => continue,
+        };
+
+        if resolver.is_hidden(&name) || name == NOINDEX_FILE {
+            continue;
+        }
+
+        // -> Only list what a request for the entry would actually serve
+        let resolved = match resolver.check(&dir.join(&name)) {
+            Ok(p) => p,
+            Err(_) => continue,
+        };
+
+        let metadata = match fs::metadata(&resolved) {
+            Ok(m) => m,
+            Err(_) => continue,
+        };
+
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs());
+
+        entries.push(Entry {
+            name,
+            directory: metadata.is_dir(),
+            size: match metadata.is_dir() {
+                true => None,
+                false => Some(metadata.len()),
+            },
+            modified,
+        });
+    }
+
+    Ok(entries)
+}
+
+// -> Directories always come first, the order only applies within each group
+fn sort_entries(entries: &mut [Entry], sort: SortKey, descending: bool) {
+    entries.sort_by(|a, b| {
+        let order = match sort {
+            SortKey::Name => Ordering::Equal,
+            SortKey::Size => a.size.cmp(&b.size),
+            SortKey::Modified => a.modified.cmp(&b.modified),
+        }
+        .then_with(|| a.name.cmp(&b.name));
+
+        let order = match descending {
+            true => order.reverse(),
+            false => order,
+        };
+
+        b.directory.cmp(&a.directory).then(order)
+    });
+}
+
+fn prefers_json(accept: Option<&str>) -> bool {
+    let accept = match accept {
+        Some(a) => a,
+        None => return false,
+    };
+
+    let mut json = 0.0;
+    let mut html = 0.0;
+
+    for range in accept.split(',') {
+        let mut parts = range.split(';');
+        let media = parts.next().unwrap_or("").trim().to_ascii_lowercase();
+        let q = parts
+            .filter_map(|p| p.trim().strip_prefix("q="))
+            .filter_map(|q| q.parse::<f32>().ok())
+            .next()
+            .unwrap_or(1.0);
+
+        match media.as_str() {
+            "application/json" => json = q,
+            "text/html" => html = q,
+            "*/*" | "text/*" if html == 0.0 => html = q,
+            _ => (),
+        }
+    }
+
+    json > 0.0 && json > html
+}
+
+fn dir_path(segments: &[String]) -> String {
+    segments.iter().map(|s| format!("{s}/")).collect()
+}
+
+// -> Links are absolute so they work whether or not the request had a trailing slash
+fn href(segments: &[String]) -> String {
+    let encoded: Vec<String> = segments
+        .iter()
+        .map(|s| urlencoding::encode(s).into_owned())
+        .collect();
+    format!("/{}", encoded.join("/"))
+}
+
+fn html(segments: &[String], entries: &[Entry], sort: SortKey, descending: bool) -> String {
+    let title = format!("Index of /{}", escape(&dir_path(segments)));
+
+    // -> Clicking the active column flips the order
+    let header = |label: &str, key: SortKey, value: &str| {
+        let order = match sort == key && !descending {
+            true => "desc",
+            false => "asc",
+        };
+        format!("<th><a href=\"?sort={value}&amp;order={order}\">{label}</a></th>")
+    };
+
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n</head>\n<body>\n<h1>{title}</h1>\n<table>\n<tr>{}{}{}</tr>\n",
+        header("Name", SortKey::Name, "name"),
+        header("Size", SortKey::Size, "size"),
+        header("Modified", SortKey::Modified, "modified"),
+    );
+
+    if let Some((_, parent)) = segments.split_last() {
+        let parent = match parent.is_empty() {
+            true => String::from("/"),
+            false => format!("{}/", href(parent)),
+        };
+        out.push_str(&format!(
+            "<tr><td><a href=\"{}\">../</a></td><td></td><td></td></tr>\n",
+            escape(&parent)
+        ));
+    }
+
+    for entry in entries {
+        let suffix = match entry.directory {
+            true => "/",
+            false => "",
+        };
+        let size = match entry.size {
+            Some(s) => s.to_string(),
+            None => String::from("-"),
+        };
+        let modified = match entry.modified {
+            Some(secs) => httpdate::fmt_http_date(UNIX_EPOCH + Duration::from_secs(secs)),
+            None => String::from("-"),
+        };
+
+        let mut path = segments.to_vec();
+        path.push(entry.name.clone());
+
+        out.push_str(&format!(
+            "<tr><td><a href=\"{}{}\">{}{}</a></td><td>{}</td><td>{}</td></tr>\n",
+            escape(&href(&path)),
+            suffix,
+            escape(&entry.name),
+            suffix,
+            size,
+            modified
+        ));
+    }
+
+    out.push_str("</table>\n</body>\n</html>\n");
+    out
+}
+
+fn escape(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+
+    for c in input.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+
+    out
+}
@@ -0,0 +1,546 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufRead, Cursor, Read, Seek, SeekFrom, Write},
+};
+
+use crate::{
+    defaults::{CHUNK_SIZE, MAX_CHUNK_LINE, MAX_DRAIN_SIZE},
+    headers::HeaderMap,
+    parser,
+    status::{ClientErrorCode, HTTPStatusCode},
+};
+
+#[derive(Debug)]
+pub enum Body {
+    Bytes(Vec<u8>),
+    // -> Open file and the number of bytes to send from its current position
+    File(File, u64),
+    // -> Open file, offset and length, the file is positioned when written
+    Range(File, u64, u64),
+    // -> Several bodies sent back to back, e.g. multipart/byteranges
+    Parts(Vec<Body>),
+}
+
+impl Body {
+    pub fn from_file(file: File) -> io::Result<Body> {
+        let length = file.metadata()?.len();
+        Ok(Body::File(file, length))
+    }
+
+    pub fn len(&self) -> u64 {
+        match self {
+            Body::Bytes(bytes) => bytes.len() as u64,
+            Body::File(_, length) => *length,
+            Body::Range(_, _, length) => *length,
+            Body::Parts(parts) => parts.iter().map(Body::len).sum(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn write_to<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        match self {
+            Body::Bytes(bytes) => writer.write_all(bytes),
+            Body::File(file, length) => copy(file, *length, writer),
+            Body::Range(file, offset, length) => {
+                file.seek(SeekFrom::Start(*offset))?;
+                copy(file, *length, writer)
+            }
+            Body::Parts(parts) => {
+                for part in parts {
+                    part.write_to(writer)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// -> Stream in fixed chunks so large files never sit in memory
+fn copy<W: Write>(file: &mut File, length: u64, writer: &mut W) -> io::Result<()> {
+    let mut buffer = vec![0; CHUNK_SIZE.min(length as usize)];
+    let mut remaining = length;
+
+    while remaining > 0 {
+        let want = remaining.min(CHUNK_SIZE as u64) as usize;
+        let read = file.read(&mut buffer[..want])?;
+
+        if read == 0 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
+
+        writer.write_all(&buffer[..read])?;
+        remaining -= read as u64;
+    }
+
+    Ok(())
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(value: Vec<u8>) -> Self {
+        Body::Bytes(value)
+    }
+}
+
+impl From<String> for Body {
+    fn from(value: String) -> Self {
+        Body::Bytes(value.into_bytes())
+    }
+}
+
+impl From<&str> for Body {
+    fn from(value: &str) -> Self {
+        Body::Bytes(value.as_bytes().to_vec())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    Empty,
+    Length(u64),
+    Chunked,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChunkState {
+    Size,
+    Data(u64),
+    Done,
+}
+
+pub struct RequestBody {
+    reader: Option<Box<dyn BufRead + Send>>,
+    framing: Framing,
+    remaining: u64,
+    chunk: ChunkState,
+    limit: u64,
+    received: u64,
+    trailers: HeaderMap,
+    failed: Option<HTTPStatusCode>,
+}
+
+impl RequestBody {
+    pub fn empty() -> RequestBody {
+        RequestBody::new(None, Framing::Empty, 0)
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> RequestBody {
+        let length = bytes.len() as u64;
+        RequestBody::new(
+            Some(Box::new(Cursor::new(bytes))),
+            Framing::Length(length),
+            length,
+        )
+    }
+
+    pub fn from_reader(
+        reader: Box<dyn BufRead + Send>,
+        framing: Framing,
+        limit: u64,
+    ) -> RequestBody {
+        RequestBody::new(Some(reader), framing, limit)
+    }
+
+    fn new(reader: Option<Box<dyn BufRead + Send>>, framing: Framing, limit: u64) -> RequestBody {
+        let remaining = match framing {
+            Framing::Length(length) => length,
+            _ => 0,
+        };
+
+        RequestBody {
+            reader,
+            framing,
+            remaining,
+            chunk: ChunkState::Size,
+            limit,
+            received: 0,
+            trailers: HeaderMap::new(),
+            failed: None,
+        }
+    }
+
+    pub fn framing(&self) -> Framing {
+        self.framing
+    }
+
+    // -> Declared size for Content-Length bodies, unknown for chunked ones
+    pub fn content_length(&self) -> Option<u64> {
+        match self.framing {
+            Framing::Empty => Some(0),
+            Framing::Length(length) => Some(length),
+            Framing::Chunked => None,
+        }
+    }
+
+    pub fn trailers(&self) -> &HeaderMap {
+        &self.trailers
+    }
+
+    pub fn error(&self) -> Option<HTTPStatusCode> {
+        self.failed
+    }
+
+    pub fn buffer(&mut self) -> Result<Vec<u8>, HTTPStatusCode> {
+        let mut buffer = Vec::new();
+
+        match self.read_to_end(&mut buffer) {
+            Ok(_) => Ok(buffer),
+            Err(_) => Err(self
+                .failed
+                .unwrap_or(HTTPStatusCode::ClientError(ClientErrorCode::BadRequest))),
+        }
+    }
+
+    // -> Skips what the handler left unread and hands the connection back for the next request
+    pub fn finish(&mut self) -> Option<Box<dyn BufRead + Send>> {
+        if self.failed.is_some() {
+            return None;
+        }
+
+        let unread = match self.framing {
+            Framing::Empty => 0,
+            Framing::Length(_) => self.remaining,
+            Framing::Chunked => match self.chunk {
+                ChunkState::Done => 0,
+                _ => MAX_DRAIN_SIZE,
+            },
+        };
+
+        if unread > MAX_DRAIN_SIZE {
+            return None;
+        }
+
+        let mut sink = Vec::new();
+        match self
+            .by_ref()
+            .take(MAX_DRAIN_SIZE + 1)
+            .read_to_end(&mut sink)
+        {
+            Ok(n) if (n as u64) <= MAX_DRAIN_SIZE => self.reader.take(),
+            _ => None,
+        }
+    }
+
+    fn fail(&mut self, status: HTTPStatusCode, message: &str) -> io::Error {
+        self.failed = Some(status);
+        io::Error::new(io::ErrorKind::InvalidData, String::from(message))
+    }
+
+    fn bad_request(&mut self, message: &str) -> io::Error {
+        self.fail(
+            HTTPStatusCode::ClientError(ClientErrorCode::BadRequest),
+            message,
+        )
+    }
+
+    fn read_length(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.remaining == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let want = buf
+            .len()
+            .min(self.remaining.min(usize::MAX as u64) as usize);
+        let read = match &mut self.reader {
+            Some(r) => r.read(&mut buf[..want])?,
+            None => 0,
+        };
+
+        if read == 0 {
+            return Err(self.bad_request("body ended before Content-Length"));
+        }
+
+        self.remaining -= read as u64;
+        Ok(read)
+    }
+
+    fn read_chunked(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // https://www.rfc-editor.org/rfc/rfc9112#name-chunked-transfer-coding
+        loop {
+            match self.chunk {
+                ChunkState::Done => return Ok(0),
+                ChunkState::Size => {
+                    let line = self.read_chunk_line()?;
+                    let size = match parse_chunk_size(&line) {
+                        Some(s) => s,
+                        None => return Err(self.bad_request("invalid chunk size")),
+                    };
+
+                    if size == 0 {
+                        let reader = match &mut self.reader {
+                            Some(r) => r,
+                            None => return Err(self.bad_request("missing body")),
+                        };
+
+                        // -> Trailer section uses the same rules as the header section
+                        match parser::parse_headers(reader) {
+                            Ok(trailers) => self.trailers = trailers,
+                            Err(e) => return Err(self.fail(e.status(), "invalid trailer section")),
+                        }
+
+                        self.chunk = ChunkState::Done;
+                        return Ok(0);
+                    }
+
+                    if self.received.saturating_add(size) > self.limit {
+                        return Err(self.fail(
+                            HTTPStatusCode::ClientError(ClientErrorCode::ContentTooLarge),
+                            "body exceeds size limit",
+                        ));
+                    }
+
+                    self.received += size;
+                    self.chunk = ChunkState::Data(size);
+                }
+                ChunkState::Data(0) => {
+                    if !self.read_chunk_line()?.is_empty() {
+                        return Err(self.bad_request("missing CRLF after chunk data"));
+                    }
+
+                    self.chunk = ChunkState::Size;
+                }
+                ChunkState::Data(left) => {
+                    if buf.is_empty() {
+                        return Ok(0);
+                    }
+
+                    let want = buf.len().min(left.min(usize::MAX as u64) as usize);
+                    let read = match &mut self.reader {
+                        Some(r) => r.read(&mut buf[..want])?,
+                        None => 0,
+                    };
+
+                    if read == 0 {
+                        return Err(self.bad_request("body ended inside a chunk"));
+                    }
+
+                    self.chunk = ChunkState::Data(left - read as u64);
+                    return Ok(read);
+                }
+            }
+        }
+    }
+
+    fn read_chunk_line(&mut self) -> io::Result<Vec<u8>> {
+        let mut line = Vec::new();
+
+        if let Some(r) = &mut self.reader {
+            r.by_ref()
+                .take(MAX_CHUNK_LINE as u64)
+                .read_until(b'\n', &mut line)?;
+        }
+
+        if line.pop() != Some(b'\n') {
+            return Err(self.bad_request("unterminated chunk line"));
+        }
+
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+
+        Ok(line)
+    }
+}
+
+impl Read for RequestBody {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.failed.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "request body failed",
+            ));
+        }
+
+        match self.framing {
+            Framing::Empty => Ok(0),
+            Framing::Length(_) => self.read_length(buf),
+            Framing::Chunked => self.read_chunked(buf),
+        }
+    }
+}
+
+impl fmt::Debug for RequestBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestBody")
+            .field("framing", &self.framing)
+            .field("limit", &self.limit)
+            .field("trailers", &self.trailers)
+            .finish()
+    }
+}
+
+fn parse_chunk_size(line: &[u8]) -> Option<u64> {
+    // -> chunk-size [ chunk-ext ], extensions are ignored
+    let size = match line.iter().position(|b| *b == b';') {
+        Some(i) => &line[..i],
+        None => line,
+    };
+    let size = size.trim_ascii();
+
+    if size.is_empty() || size.len() > 16 || !size.iter().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    u64::from_str_radix(std::str::from_utf8(size).ok()?, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunked(input: &str, limit: u64) -> RequestBody {
+        let reader = Box::new(Cursor::new(input.as_bytes().to_vec()));
+        RequestBody::from_reader(reader, Framing::Chunked, limit)
+    }
+
+    fn bad_request() -> Option<HTTPStatusCode> {
+        Some(HTTPStatusCode::ClientError(ClientErrorCode::BadRequest))
+    }
+
+    // -> What is left on the connection once the body was handed back
+    fn rest(reader: Option<Box<dyn BufRead + Send>>) -> String {
+        let mut rest = String::new();
+        reader.unwrap().read_to_string(&mut rest).unwrap();
+        rest
+    }
+
+    #[test]
+    fn decodes_chunks() {
+        let mut body = chunked("5\r\nhello\r\n7\r\n, world\r\n0\r\n\r\n", 100);
+        assert_eq!(body.buffer(), Ok(b"hello, world".to_vec()));
+        assert!(body.trailers().is_empty());
+    }
+
+    #[test]
+    fn ignores_chunk_extensions() {
+        let mut body = chunked("5;name=value\r\nhello\r\n0;last\r\n\r\n", 100);
+        assert_eq!(body.buffer(), Ok(b"hello".to_vec()));
+    }
+
+    #[test]
+    fn accepts_bare_lf_and_uppercase_hex() {
+        let mut body = chunked("A\nabcdefghij\n0\n\n", 100);
+        assert_eq!(body.buffer(), Ok(b"abcdefghij".to_vec()));
+    }
+
+    #[test]
+    fn reads_trailers() {
+        let mut body = chunked("3\r\nabc\r\n0\r\nChecksum: 123\r\nX-Done: yes\r\n\r\n", 100);
+        assert_eq!(body.buffer(), Ok(b"abc".to_vec()));
+        assert_eq!(body.trailers().get("Checksum"), Some("123"));
+        assert_eq!(body.trailers().get("X-Done"), Some("yes"));
+    }
+
+    #[test]
+    fn refuses_malformed_trailers() {
+        let mut body = chunked("3\r\nabc\r\n0\r\nno colon\r\n\r\n", 100);
+        assert!(body.buffer().is_err());
+        assert_eq!(body.error(), bad_request());
+    }
+
+    #[test]
+    fn refuses_invalid_chunk_sizes() {
+        for input in [
+            "\r\n",
+            "x\r\n",
+            "-1\r\n",
+            "0x5\r\nhello\r\n0\r\n\r\n",
+            "+5\r\nhello\r\n0\r\n\r\n",
+            "11111111111111111\r\n",
+        ] {
+            let mut body = chunked(input, u64::MAX);
+            assert!(body.buffer().is_err(), "{input:?}");
+            assert_eq!(body.error(), bad_request(), "{input:?}");
+        }
+    }
+
+    #[test]
+    fn refuses_chunks_over_the_limit() {
+        // -> Refused on the size line, before any of the data is read
+        let mut body = chunked("4\r\nabcd\r\n4\r\nefgh\r\n0\r\n\r\n", 6);
+        assert!(body.buffer().is_err());
+        assert_eq!(
+            body.error(),
+            Some(HTTPStatusCode::ClientError(
+                ClientErrorCode::ContentTooLarge
+            ))
+        );
+        assert!(body.finish().is_none());
+    }
+
+    #[test]
+    fn refuses_truncated_chunks() {
+        let mut body = chunked("a\r\nshort", 100);
+        assert!(body.buffer().is_err());
+        assert_eq!(body.error(), bad_request());
+
+        let mut body = chunked("5\r\nhello", 100);
+        assert!(body.buffer().is_err());
+        assert_eq!(body.error(), bad_request());
+    }
+
+    #[test]
+    fn refuses_missing_crlf_after_data() {
+        let mut body = chunked("3\r\nabcdef\r\n0\r\n\r\n", 100);
+        assert!(body.buffer().is_err());
+        assert_eq!(body.error(), bad_request());
+    }
+
+    #[test]
+    fn refuses_overlong_chunk_lines() {
+        let extension = "x".repeat(MAX_CHUNK_LINE);
+        let mut body = chunked(&format!("3;{extension}\r\nabc\r\n0\r\n\r\n"), 100);
+        assert!(body.buffer().is_err());
+        assert_eq!(body.error(), bad_request());
+    }
+
+    #[test]
+    fn drains_unread_chunks_before_the_next_request() {
+        let mut body = chunked("5\r\nhello\r\n0\r\nX: y\r\n\r\nGET / HTTP/1.1\r\n", 100);
+        let mut first = [0; 2];
+        body.read_exact(&mut first).unwrap();
+
+        assert_eq!(rest(body.finish()), "GET / HTTP/1.1\r\n");
+    }
+
+    #[test]
+    fn drains_unread_length_body_before_the_next_request() {
+        let reader = Box::new(Cursor::new(b"helloGET / HTTP/1.1\r\n".to_vec()));
+        let mut body = RequestBody::from_reader(reader, Framing::Length(5), 100);
+
+        assert_eq!(rest(body.finish()), "GET / HTTP/1.1\r\n");
+    }
+
+    #[test]
+    fn closes_instead_of_draining_large_bodies() {
+        let length = MAX_DRAIN_SIZE + 1;
+        let reader = Box::new(Cursor::new(vec![b'a'; length as usize]));
+        let mut body = RequestBody::from_reader(reader, Framing::Length(length), u64::MAX);
+
+        assert!(body.finish().is_none());
+    }
+
+    #[test]
+    fn refuses_length_body_that_ends_early() {
+        let reader = Box::new(Cursor::new(b"abc".to_vec()));
+        let mut body = RequestBody::from_reader(reader, Framing::Length(5), 100);
+
+        assert!(body.buffer().is_err());
+        assert_eq!(body.error(), bad_request());
+        assert!(body.finish().is_none());
+    }
+
+    #[test]
+    fn parses_chunk_sizes() {
+        assert_eq!(parse_chunk_size(b"0"), Some(0));
+        assert_eq!(parse_chunk_size(b"1f"), Some(31));
+        assert_eq!(parse_chunk_size(b"FF ; ext"), Some(255));
+        assert_eq!(parse_chunk_size(b"ffffffffffffffff"), Some(u64::MAX));
+        assert_eq!(parse_chunk_size(b""), None);
+        assert_eq!(parse_chunk_size(b";ext"), None);
+        assert_eq!(parse_chunk_size(b"1 2"), None);
+    }
+}
@@ -0,0 +1,119 @@
+use std::path::PathBuf;
+
+use crate::LogLevel;
+
+pub const USAGE: &str = concat!(
+    env!("CARGO_PKG_NAME"),
+    " ",
+    env!("CARGO_PKG_VERSION"),
+    "
+
+Usage:
+    rust-web-server [OPTIONS]
+    rust-web-server serve [DIR] [OPTIONS]
+    rust-web-server check-config [OPTIONS]
+
+Commands:
+    serve [DIR]        Serve DIR, or the configured root (default)
+    check-config       Validate the configuration and exit
+
+Options:
+    -p, --port PORT        Port to listen on
+    -H, --host HOST        Address to listen on, may be repeated
+    -l, --listen ADDR      host:port to listen on, may be repeated
+    -r, --root DIR         Directory to serve
+        --log-level LEVEL  error, warn, info or debug
+    -c, --config FILE      Config file to load
+        --print-config     Print the effective config and exit
+    -h, --help             Print this help
+    -V, --version          Print the version
+"
+);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Command {
+    #[default]
+    Serve,
+    CheckConfig,
+    Help,
+    Version,
+}
+
+#[derive(Debug, Default)]
+pub struct Args {
+    pub command: Command,
+    pub hosts: Vec<String>,
+    pub port: Option<u16>,
+    pub listen: Vec<String>,
+    pub root: Option<PathBuf>,
+    pub log_level: Option<LogLevel>,
+    pub config: Option<PathBuf>,
+    pub print_config: bool,
+}
+
+impl Args {
+    pub fn parse<I: IntoIterator<Item = String>>(input: I) -> Result<Args, String> {
+        let mut args = Args::default();
+        let mut input = input.into_iter();
+        let mut command: Option<Command> = None;
+
+        while let Some(arg) = input.next() {
+            // -> "--port 80" and "--port=80" are both accepted
+            let (flag, inline) = match arg.split_once('=') {
+                Some((f, v)) if f.starts_with("--") => (String::from(f), Some(String::from(v))),
+                _ => (arg.clone(), None),
+            };
+
+            let mut value = |name: &str| match inline.clone().or_else(|| input.next()) {
+                Some(v) => Ok(v),
+                None => Err(format!("{name} needs a value")),
+            };
+
+            match flag.as_str() {
+                "--host" | "-H" => args.hosts.push(value(&flag)?),
+                "--port" | "-p" => {
+                    let raw = value(&flag)?;
+                    match raw.parse::<u16>() {
+                        Ok(p) => args.port = Some(p),
+                        Err(_) => return Err(format!("\"{raw}\" is not a valid port number")),
+                    }
+                }
+                "--listen" | "-l" => args.listen.push(value(&flag)?),
+                "--root" | "-r" => args.root = Some(PathBuf::from(value(&flag)?)),
+                "--log-level" => {
+                    let raw = value(&flag)?;
+                    match raw.parse::<LogLevel>() {
+                        Ok(l) => args.log_level = Some(l),
+                        Err(_) => return Err(format!("\"{raw}\" is not a log level")),
+                    }
+                }
+                "--config" | "-c" => args.config = Some(PathBuf::from(value(&flag)?)),
+                "--print-config" => args.print_config = true,
+                "--help" | "-h" => return Ok(Args::only(Command::Help)),
+                "--version" | "-V" => return Ok(Args::only(Command::Version)),
+                f if f.starts_with('-') && f.len() > 1 => {
+                    return Err(format!("Unknown option \"{arg}\""));
+                }
+                _ => match command {
+                    None if arg == "serve" => command = Some(Command::Serve),
+                    None if arg == "check-config" => command = Some(Command::CheckConfig),
+                    Some(Command::Serve) if args.root.is_none() => {
+                        args.root = Some(PathBuf::from(arg));
+                    }
+                    _ => return Err(format!("Unexpected argument \"{arg}\"")),
+                },
+            }
+        }
+
+        args.command = command.unwrap_or_default();
+
+        Ok(args)
+    }
+
+    fn only(command: Command) -> Args {
+        Args {
+            command,
+            ..Args::default()
+        }
+    }
+}
@@ -0,0 +1,483 @@
+use std::io::{self, Write};
+
+use brotli::CompressorWriter;
+use flate2::{
+    Compression as Level,
+    write::{GzEncoder, ZlibEncoder},
+};
+
+use crate::{
+    HTTPRequest, HTTPResponse,
+    body::Body,
+    config::ServerConfig,
+    defaults::{COMPRESSION_MIN_SIZE, MAX_COMPRESS_SIZE},
+    handler::{Handler, Middleware},
+    headers::HeaderMap,
+    mime,
+    status::{HTTPStatusCode, RedirectionCode, ServerErrorCode, SuccessCode},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    Brotli,
+    Gzip,
+    Deflate,
+}
+
+impl Encoding {
+    // -> Server preference when the client rates several codings the same
+    pub const ALL: [Encoding; 3] = [Encoding::Brotli, Encoding::Gzip, Encoding::Deflate];
+
+    pub fn token(&self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
+    }
+
+    // -> File name suffix of a precompressed sibling, deflate has none
+    pub fn extension(&self) -> Option<&'static str> {
+        match self {
+            Encoding::Brotli => Some("br"),
+            Encoding::Gzip => Some("gz"),
+            Encoding::Deflate => None,
+        }
+    }
+
+    pub fn encode(&self, body: Body) -> io::Result<Vec<u8>> {
+        let mut body = body;
+        let capacity = (body.len() / 2) as usize;
+
+        match self {
+            Encoding::Brotli => {
+                let mut encoder = CompressorWriter::new(Vec::with_capacity(capacity), 4096, 5, 22);
+                body.write_to(&mut encoder)?;
+                encoder.flush()?;
+                Ok(encoder.into_inner())
+            }
+            Encoding::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::with_capacity(capacity), Level::default());
+                body.write_to(&mut encoder)?;
+                encoder.finish()
+            }
+            Encoding::Deflate => {
+                // -> HTTP "deflate" is the zlib format, not raw deflate
+                let mut encoder = ZlibEncoder::new(Vec::with_capacity(capacity), Level::default());
+                body.write_to(&mut encoder)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+// -> Picks the best coding out of `available` by Accept-Encoding q-values, None means identity
+pub fn negotiate(accept: Option<&str>, available: &[Encoding]) -> Option<Encoding> {
+    let accept = accept?;
+
+    let mut wildcard: Option<f32> = None;
+    let mut rated: Vec<(String, f32)> = Vec::new();
+
+    for item in accept.split(',') {
+        let mut parts = item.split(';');
+        let coding = parts.next().unwrap_or("").trim().to_ascii_lowercase();
+        // -> "Q=" counts too, values outside 0..1 (or "NaN") are ignored
+        let q = parts
+            .filter_map(|p| p.split_once('='))
+            .filter(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+            .filter_map(|(_, q)| q.trim().parse::<f32>().ok())
+            .find(|q| (0.0..=1.0).contains(q))
+            .unwrap_or(1.0);
+
+        match coding.as_str() {
+            "" => (),
+            "*" => wildcard = Some(q),
+            // -> x-gzip is an old alias from RFC 9110 8.4.1.3
+            "x-gzip" => rated.push((String::from("gzip"), q)),
+            _ => rated.push((coding, q)),
+        }
+    }
+
+    let mut best: Option<(Encoding, f32)> = None;
+
+    for encoding in available {
+        let q = match rated.iter().find(|(c, _)| c == encoding.token()) {
+            Some((_, q)) => *q,
+            None => wildcard.unwrap_or(0.0),
+        };
+
+        if q > 0.0 && best.is_none_or(|(_, current)| q > current) {
+            best = Some((*encoding, q));
+        }
+    }
+
+    best.map(|(encoding, _)| encoding)
+}
+
+// -> "text/*" matches any text type, everything else has to match exactly
+pub fn type_matches(patterns: &[String], content_type: &str) -> bool {
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+
+    patterns.iter().any(|p| match p.strip_suffix("/*") {
+        Some(prefix) => essence.split('/').next() == Some(prefix),
+        None => *p == essence,
+    })
+}
+
+pub fn default_types() -> Vec<String> {
+    let mut types = vec![String::from("text/*")];
+    types.extend(mime::TEXT_TYPES.iter().map(|t| String::from(*t)));
+    types
+}
+
+// -> Adds a token to Vary unless it is already listed
+pub fn add_vary(headers: &mut HeaderMap, token: &str) {
+    let present = headers
+        .get_all("Vary")
+        .iter()
+        .flat_map(|v| v.split(','))
+        .any(|t| t.trim() == "*" || t.trim().eq_ignore_ascii_case(token));
+
+    if !present {
+        headers.append("Vary", token);
+    }
+}
+
+// -> Compresses responses on the fly, ones that already have a Content-Encoding are left alone
+pub struct Compression {
+    min_size: u64,
+    types: Vec<String>,
+    encodings: Vec<Encoding>,
+}
+
+impl Compression {
+    pub fn new() -> Compression {
+        Compression {
+            min_size: COMPRESSION_MIN_SIZE,
+            types: default_types(),
+            encodings: Encoding::ALL.to_vec(),
+        }
+    }
+
+    pub fn from_config(config: &ServerConfig) -> Compression {
+        Compression::new()
+            .min_size(config.compression_min_size)
+            .types(config.compression_types.clone())
+    }
+
+    pub fn min_size(mut self, min_size: u64) -> Compression {
+        self.min_size = min_size;
+        self
+    }
+
+    pub fn types(mut self, types: Vec<String>) -> Compression {
+        self.types = types;
+        self
+    }
+
+    pub fn encodings(mut self, encodings: Vec<Encoding>) -> Compression {
+        self.encodings = encodings;
+        self
+    }
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Compression::new()
+    }
+}
+
+impl Middleware for Compression {
+    fn handle(&self, request: &mut HTTPRequest, next: &dyn Handler) -> HTTPResponse {
+        let mut response = next.handle(request);
+
+        // -> A 304 has no type to go by, it keeps the tag of the copy the client has cached
+        if response.status == HTTPStatusCode::Redirection(RedirectionCode::NotModified) {
+            if let Some(etag) = response.headers.get("ETag")
+                && !etag.starts_with("W/")
+            {
+                let weak = format!("W/{etag}");
+                let cached = request
+                    .headers
+                    .get_all("If-None-Match")
+                    .iter()
+                    .flat_map(|v| v.split(','))
+                    .any(|tag| tag.trim() == weak);
+
+                if cached {
+                    response.headers.insert("ETag", weak);
+                }
+            }
+            return response;
+        }
+
+        let compressible = match response.headers.get("Content-Type") {
+            Some(t) => type_matches(&self.types, t),
+            None => false,
+        };
+
+        if !compressible || response.headers.contains("Content-Encoding") {
+            return response;
+        }
+
+        // -> The representation depends on Accept-Encoding even when this one went out plain
+        add_vary(&mut response.headers, "Accept-Encoding");
+
+        let encoding = match negotiate(request.headers.get("Accept-Encoding"), &self.encodings) {
+            Some(e) => e,
+            None => return response,
+        };
+
+        // -> Only full 200 bodies, a 206 describes byte offsets of the identity representation
+        let size = match &response.contents {
+            Some(body) if response.status == HTTPStatusCode::Success(SuccessCode::OK) => body.len(),
+            _ => return response,
+        };
+
+        if size < self.min_size || size > MAX_COMPRESS_SIZE {
+            return response;
+        }
+
+        let body = match response.contents.take() {
+            Some(b) => b,
+            None => return response,
+        };
+
+        let compressed = match encoding.encode(body) {
+            Ok(c) => c,
+            Err(_) => {
+                return HTTPResponse::builder()
+                    .status(HTTPStatusCode::ServerError(
+                        ServerErrorCode::InternalServerError,
+                    ))
+                    .build();
+            }
+        };
+
+        response.headers.remove("Content-Length");
+        response.headers.remove("Accept-Ranges");
+        response
+            .headers
+            .insert("Content-Encoding", encoding.token());
+        weaken_etag(&mut response.headers);
+
+        response.contents = Some(Body::from(compressed));
+        response
+    }
+}
+
+// -> The bytes differ from the file, so a strong tag would be a lie
+fn weaken_etag(headers: &mut HeaderMap) {
+    if let Some(etag) = headers.get("ETag")
+        && !etag.starts_with("W/")
+    {
+        let weak = format!("W/{etag}");
+        headers.insert("ETag", weak);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::{Cursor, Read};
+
+    use flate2::read::GzDecoder;
+
+    use crate::{parser::parse_request, status::ClientErrorCode};
+
+    const ALL: [Encoding; 3] = Encoding::ALL;
+
+    fn request(accept: Option<&str>) -> HTTPRequest {
+        let mut input = String::from("GET / HTTP/1.1\r\nHost: a.test\r\n");
+        if let Some(accept) = accept {
+            input.push_str(&format!("Accept-Encoding: {accept}\r\n"));
+        }
+        input.push_str("\r\n");
+        parse_request(&mut Cursor::new(input.into_bytes())).unwrap()
+    }
+
+    fn page(status: HTTPStatusCode) -> impl Handler {
+        move |_: &mut HTTPRequest| {
+            HTTPResponse::builder()
+                .status(status)
+                .header("Content-Type", "text/html; charset=utf-8")
+                .header("ETag", "\"abc\"")
+                .body("<p>hello</p>".repeat(200))
+                .build()
+        }
+    }
+
+    fn body(response: HTTPResponse) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        response.contents.unwrap().write_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn negotiates_by_q_value() {
+        assert_eq!(negotiate(Some("gzip, br"), &ALL), Some(Encoding::Brotli));
+        assert_eq!(
+            negotiate(Some("gzip;q=1, br;q=0.5"), &ALL),
+            Some(Encoding::Gzip)
+        );
+        assert_eq!(
+            negotiate(Some("deflate;q=0.9, gzip;q=0.8"), &ALL),
+            Some(Encoding::Deflate)
+        );
+        assert_eq!(negotiate(Some("br;q=0.5"), &[Encoding::Gzip]), None);
+        assert_eq!(negotiate(None, &ALL), None);
+        assert_eq!(negotiate(Some(""), &ALL), None);
+    }
+
+    #[test]
+    fn q_zero_excludes_a_coding() {
+        assert_eq!(negotiate(Some("br;q=0, gzip"), &ALL), Some(Encoding::Gzip));
+        assert_eq!(
+            negotiate(Some("br;q=0, gzip;q=0.0, deflate;q=0"), &ALL),
+            None
+        );
+    }
+
+    #[test]
+    fn wildcard_rates_unlisted_codings() {
+        assert_eq!(negotiate(Some("*"), &ALL), Some(Encoding::Brotli));
+        assert_eq!(negotiate(Some("br;q=0, *"), &ALL), Some(Encoding::Gzip));
+        assert_eq!(negotiate(Some("*;q=0"), &ALL), None);
+        assert_eq!(
+            negotiate(Some("gzip;q=0.1, *;q=0.5"), &ALL),
+            Some(Encoding::Brotli)
+        );
+        // -> Refusing identity alone doesn't make any other coding acceptable
+        assert_eq!(negotiate(Some("identity;q=0"), &ALL), None);
+        assert_eq!(
+            negotiate(Some("identity;q=0, *"), &ALL),
+            Some(Encoding::Brotli)
+        );
+    }
+
+    #[test]
+    fn matches_codings_and_parameters_case_insensitively() {
+        assert_eq!(negotiate(Some("GZIP"), &ALL), Some(Encoding::Gzip));
+        assert_eq!(
+            negotiate(Some("Br;Q=0, Gzip;Q=0.5"), &ALL),
+            Some(Encoding::Gzip)
+        );
+        assert_eq!(negotiate(Some("x-gzip"), &ALL), Some(Encoding::Gzip));
+    }
+
+    #[test]
+    fn ignores_invalid_q_values() {
+        assert_eq!(
+            negotiate(Some("br;q=nan, gzip;q=0.5"), &ALL),
+            Some(Encoding::Brotli)
+        );
+        assert_eq!(
+            negotiate(Some("br;q=0.5, gzip;q=inf"), &ALL),
+            Some(Encoding::Gzip)
+        );
+        assert_eq!(
+            negotiate(Some("br;q=0.5, gzip;q=2"), &ALL),
+            Some(Encoding::Gzip)
+        );
+    }
+
+    #[test]
+    fn matches_content_types() {
+        let types = default_types();
+
+        assert!(type_matches(&types, "text/plain"));
+        assert!(type_matches(&types, "Text/HTML; charset=utf-8"));
+        assert!(type_matches(&types, "application/json"));
+        assert!(!type_matches(&types, "image/png"));
+        assert!(!type_matches(&types, "textual/plain"));
+    }
+
+    #[test]
+    fn adds_vary_once() {
+        let mut headers = HeaderMap::new();
+        add_vary(&mut headers, "Accept-Encoding");
+        add_vary(&mut headers, "accept-encoding");
+        assert_eq!(headers.get_all("Vary"), vec!["Accept-Encoding"]);
+
+        let mut headers = HeaderMap::new();
+        headers.append("Vary", "*");
+        add_vary(&mut headers, "Accept-Encoding");
+        assert_eq!(headers.get_all("Vary"), vec!["*"]);
+    }
+
+    #[test]
+    fn compresses_ok_responses() {
+        let next = page(HTTPStatusCode::Success(SuccessCode::OK));
+        let response = Compression::new().handle(&mut request(Some("gzip")), &next);
+
+        assert_eq!(response.headers.get("Content-Encoding"), Some("gzip"));
+        assert_eq!(response.headers.get("Vary"), Some("Accept-Encoding"));
+        assert_eq!(response.headers.get("ETag"), Some("W/\"abc\""));
+
+        let mut decoded = String::new();
+        GzDecoder::new(body(response).as_slice())
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, "<p>hello</p>".repeat(200));
+    }
+
+    #[test]
+    fn varies_even_when_sent_plain() {
+        let next = page(HTTPStatusCode::Success(SuccessCode::OK));
+        let response = Compression::new().handle(&mut request(None), &next);
+
+        assert_eq!(response.headers.get("Content-Encoding"), None);
+        assert_eq!(response.headers.get("Vary"), Some("Accept-Encoding"));
+        assert_eq!(response.headers.get("ETag"), Some("\"abc\""));
+    }
+
+    #[test]
+    fn skips_responses_other_than_ok() {
+        for status in [
+            HTTPStatusCode::Success(SuccessCode::PartialContent),
+            HTTPStatusCode::ClientError(ClientErrorCode::NotFound),
+        ] {
+            let next = page(status);
+            let response = Compression::new().handle(&mut request(Some("gzip")), &next);
+
+            assert_eq!(response.headers.get("Content-Encoding"), None);
+            assert_eq!(response.headers.get("ETag"), Some("\"abc\""));
+        }
+    }
+
+    #[test]
+    fn skips_small_and_unlisted_responses() {
+        let next = page(HTTPStatusCode::Success(SuccessCode::OK));
+        let response = Compression::new()
+            .min_size(1024 * 1024)
+            .handle(&mut request(Some("gzip")), &next);
+        assert_eq!(response.headers.get("Content-Encoding"), None);
+
+        let response = Compression::new()
+            .types(vec![String::from("image/svg+xml")])
+            .handle(&mut request(Some("gzip")), &next);
+        assert_eq!(response.headers.get("Content-Encoding"), None);
+        assert_eq!(response.headers.get("Vary"), None);
+    }
+
+    #[test]
+    fn weakens_the_tag_of_a_not_modified_for_a_compressed_copy() {
+        let next = page(HTTPStatusCode::Redirection(RedirectionCode::NotModified));
+
+        let mut cached = request(Some("gzip"));
+        cached.headers.append("If-None-Match", "W/\"abc\"");
+        let response = Compression::new().handle(&mut cached, &next);
+        assert_eq!(response.headers.get("ETag"), Some("W/\"abc\""));
+
+        let mut plain = request(Some("gzip"));
+        plain.headers.append("If-None-Match", "\"abc\"");
+        let response = Compression::new().handle(&mut plain, &next);
+        assert_eq!(response.headers.get("ETag"), Some("\"abc\""));
+    }
+}
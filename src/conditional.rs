@@ -0,0 +1,555 @@
+use std::{
+    fmt,
+    fs::Metadata,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    HTTPMethod, HTTPResponse,
+    headers::HeaderMap,
+    status::{ClientErrorCode, HTTPStatusCode, RedirectionCode},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EtagMode {
+    // -> Byte-for-byte identical representations share a tag
+    #[default]
+    Strong,
+    // -> W/"..." tags, only usable for If-None-Match
+    Weak,
+    Off,
+}
+
+impl FromStr for EtagMode {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<EtagMode, String> {
+        match input.to_ascii_lowercase().as_str() {
+            "strong" => Ok(EtagMode::Strong),
+            "weak" => Ok(EtagMode::Weak),
+            "off" | "none" => Ok(EtagMode::Off),
+            s => Err(String::from(s)),
+        }
+    }
+}
+
+impl fmt::Display for EtagMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EtagMode::Strong => write!(f, "strong"),
+            EtagMode::Weak => write!(f, "weak"),
+            EtagMode::Off => write!(f, "off"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<SystemTime>,
+}
+
+impl Validators {
+    // -> The tag is built from size and modification time, so the file is never read for it
+    pub fn from_metadata(metadata: &Metadata, mode: EtagMode) -> Validators {
+        let modified = metadata.modified().ok();
+
+        let etag = match (
+            mode,
+            modified.and_then(|m| m.duration_since(UNIX_EPOCH).ok()),
+        ) {
+            (EtagMode::Off, _) | (_, None) => None,
+            (mode, Some(time)) => {
+                let tag = format!(
+                    "\"{:x}-{:x}-{:x}\"",
+                    metadata.len(),
+                    time.as_secs(),
+                    time.subsec_nanos()
+                );
+                match mode {
+                    EtagMode::Weak => Some(format!("W/{tag}")),
+                    _ => Some(tag),
+                }
+            }
+        };
+
+        Validators {
+            etag,
+            last_modified: modified,
+        }
+    }
+
+    pub fn apply(&self, headers: &mut HeaderMap) {
+        if let Some(etag) = &self.etag {
+            headers.insert("ETag", etag.as_str());
+        }
+
+        if let Some(modified) = self.last_modified {
+            headers.insert("Last-Modified", httpdate::fmt_http_date(modified));
+        }
+    }
+
+    // -> RFC 9110 13.2.2, None means the request goes ahead normally
+    pub fn evaluate(&self, method: HTTPMethod, headers: &HeaderMap) -> Option<HTTPResponse> {
+        let safe = matches!(method, HTTPMethod::GET | HTTPMethod::HEAD);
+
+        // -> Step 1 and 2, If-Unmodified-Since only counts without If-Match
+        if let Some(value) = combined(headers, "If-Match") {
+            if !self.matches(&value, true) {
+                return Some(self.respond(precondition_failed()));
+            }
+        } else if let Some(since) = date(headers, "If-Unmodified-Since")
+            && self.modified_after(since)
+        {
+            return Some(self.respond(precondition_failed()));
+        }
+
+        // -> Step 3 and 4, If-Modified-Since only counts without If-None-Match
+        if let Some(value) = combined(headers, "If-None-Match") {
+            if self.matches(&value, false) {
+                return Some(self.respond(match safe {
+                    true => not_modified(),
+                    false => precondition_failed(),
+                }));
+            }
+        } else if safe
+            && let Some(since) = date(headers, "If-Modified-Since")
+            && !self.modified_after(since)
+        {
+            return Some(self.respond(not_modified()));
+        }
+
+        None
+    }
+
+    // -> RFC 9110 13.1.5, a Range only applies while the representation is unchanged
+    pub fn if_range(&self, value: &str) -> bool {
+        let value = value.trim();
+
+        if value.starts_with('"') || value.starts_with("W/") {
+            return !value.starts_with("W/") && self.matches(value, true);
+        }
+
+        match (httpdate::parse_http_date(value), self.last_modified) {
+            (Ok(date), Some(_)) => !self.modified_after(date) && !self.modified_before(date),
+            _ => false,
+        }
+    }
+
+    // -> Strong comparison for If-Match, weak comparison for If-None-Match
+    fn matches(&self, list: &str, strong: bool) -> bool {
+        if list.trim() == "*" {
+            return true;
+        }
+
+        let etag = match &self.etag {
+            Some(e) => e,
+            None => return false,
+        };
+
+        if strong && etag.starts_with("W/") {
+            return false;
+        }
+
+        list.split(',')
+            .map(str::trim)
+            .filter(|tag| !(strong && tag.starts_with("W/")))
+            .any(|tag| opaque(tag) == opaque(etag))
+    }
+
+    // -> HTTP dates have whole seconds, so the sub-second part is ignored
+    fn modified_after(&self, since: SystemTime) -> bool {
+        let seconds = |t: SystemTime| match t.duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_secs(),
+            Err(_) => 0,
+        };
+
+        match self.last_modified {
+            Some(modified) => seconds(modified) > seconds(since),
+            None => true,
+        }
+    }
+
+    fn modified_before(&self, date: SystemTime) -> bool {
+        match (self.last_modified, date.duration_since(UNIX_EPOCH)) {
+            (Some(modified), Ok(date)) => match modified.duration_since(UNIX_EPOCH) {
+                Ok(m) => m.as_secs() < date.as_secs(),
+                Err(_) => true,
+            },
+            _ => false,
+        }
+    }
+
+    fn respond(&self, mut response: HTTPResponse) -> HTTPResponse {
+        if response.status == HTTPStatusCode::Redirection(RedirectionCode::NotModified) {
+            self.apply(&mut response.headers);
+        }
+        response
+    }
+}
+
+fn not_modified() -> HTTPResponse {
+    HTTPResponse::builder()
+        .status(HTTPStatusCode::Redirection(RedirectionCode::NotModified))
+        .build()
+}
+
+fn precondition_failed() -> HTTPResponse {
+    HTTPResponse::builder()
+        .status(HTTPStatusCode::ClientError(
+            ClientErrorCode::PreconditionFailed,
+        ))
+        .build()
+}
+
+fn opaque(tag: &str) -> &str {
+    tag.strip_prefix("W/").unwrap_or(tag)
+}
+
+// -> Repeated list headers count as one comma separated value
+fn combined(headers: &HeaderMap, name: &str) -> Option<String> {
+    let values = headers.get_all(name);
+
+    match values.is_empty() {
+        true => None,
+        false => Some(values.join(",")),
+    }
+}
+
+// -> An invalid date makes the header count as absent
+fn date(headers: &HeaderMap, name: &str) -> Option<SystemTime> {
+    headers
+        .get(name)
+        .and_then(|v| httpdate::parse_http_date(v.trim()).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    const TAG: &str = "\"abc\"";
+    const WEAK: &str = "W/\"abc\"";
+
+    // -> Modified at second 1000 with some nanoseconds, the way a file would be
+    fn validators(etag: Option<&str>) -> Validators {
+        Validators {
+            etag: etag.map(String::from),
+            last_modified: Some(UNIX_EPOCH + Duration::new(1000, 500)),
+        }
+    }
+
+    fn at(seconds: u64) -> String {
+        httpdate::fmt_http_date(UNIX_EPOCH + Duration::from_secs(seconds))
+    }
+
+    fn status(validators: &Validators, method: HTTPMethod, fields: &[(&str, &str)]) -> Option<u16> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in fields {
+            headers.append(*name, *value);
+        }
+
+        validators
+            .evaluate(method, &headers)
+            .map(|r| r.status.to_value())
+    }
+
+    #[test]
+    fn goes_ahead_without_preconditions() {
+        assert_eq!(status(&validators(Some(TAG)), HTTPMethod::GET, &[]), None);
+    }
+
+    #[test]
+    fn if_match_compares_strongly() {
+        let strong = validators(Some(TAG));
+        let weak = validators(Some(WEAK));
+
+        assert_eq!(status(&strong, HTTPMethod::PUT, &[("If-Match", TAG)]), None);
+        assert_eq!(
+            status(&strong, HTTPMethod::PUT, &[("If-Match", "\"x\", \"abc\"")]),
+            None
+        );
+        assert_eq!(
+            status(&strong, HTTPMethod::PUT, &[("If-Match", WEAK)]),
+            Some(412)
+        );
+        assert_eq!(
+            status(&weak, HTTPMethod::PUT, &[("If-Match", WEAK)]),
+            Some(412)
+        );
+        assert_eq!(
+            status(&weak, HTTPMethod::PUT, &[("If-Match", TAG)]),
+            Some(412)
+        );
+        assert_eq!(
+            status(&strong, HTTPMethod::PUT, &[("If-Match", "\"x\"")]),
+            Some(412)
+        );
+    }
+
+    #[test]
+    fn if_none_match_compares_weakly() {
+        let strong = validators(Some(TAG));
+        let weak = validators(Some(WEAK));
+
+        assert_eq!(
+            status(&strong, HTTPMethod::GET, &[("If-None-Match", WEAK)]),
+            Some(304)
+        );
+        assert_eq!(
+            status(&weak, HTTPMethod::GET, &[("If-None-Match", TAG)]),
+            Some(304)
+        );
+        assert_eq!(
+            status(&weak, HTTPMethod::GET, &[("If-None-Match", WEAK)]),
+            Some(304)
+        );
+        assert_eq!(
+            status(&strong, HTTPMethod::GET, &[("If-None-Match", "\"x\"")]),
+            None
+        );
+    }
+
+    #[test]
+    fn if_none_match_fails_unsafe_methods() {
+        let validators = validators(Some(TAG));
+
+        assert_eq!(
+            status(&validators, HTTPMethod::GET, &[("If-None-Match", TAG)]),
+            Some(304)
+        );
+        assert_eq!(
+            status(&validators, HTTPMethod::HEAD, &[("If-None-Match", TAG)]),
+            Some(304)
+        );
+        assert_eq!(
+            status(&validators, HTTPMethod::PUT, &[("If-None-Match", TAG)]),
+            Some(412)
+        );
+        assert_eq!(
+            status(&validators, HTTPMethod::DELETE, &[("If-None-Match", TAG)]),
+            Some(412)
+        );
+    }
+
+    #[test]
+    fn star_matches_any_current_representation() {
+        let tagged = validators(Some(TAG));
+        let untagged = validators(None);
+
+        assert_eq!(status(&tagged, HTTPMethod::PUT, &[("If-Match", "*")]), None);
+        assert_eq!(
+            status(&untagged, HTTPMethod::PUT, &[("If-Match", "*")]),
+            None
+        );
+        assert_eq!(
+            status(&tagged, HTTPMethod::GET, &[("If-None-Match", "*")]),
+            Some(304)
+        );
+        assert_eq!(
+            status(&tagged, HTTPMethod::PUT, &[("If-None-Match", "*")]),
+            Some(412)
+        );
+    }
+
+    #[test]
+    fn combines_repeated_list_headers() {
+        let validators = validators(Some(TAG));
+
+        assert_eq!(
+            status(
+                &validators,
+                HTTPMethod::GET,
+                &[("If-None-Match", "\"x\""), ("If-None-Match", TAG)]
+            ),
+            Some(304)
+        );
+    }
+
+    #[test]
+    fn compares_dates_in_whole_seconds() {
+        let validators = validators(Some(TAG));
+
+        assert_eq!(
+            status(
+                &validators,
+                HTTPMethod::GET,
+                &[("If-Modified-Since", &at(1000))]
+            ),
+            Some(304)
+        );
+        assert_eq!(
+            status(
+                &validators,
+                HTTPMethod::GET,
+                &[("If-Modified-Since", &at(999))]
+            ),
+            None
+        );
+        assert_eq!(
+            status(
+                &validators,
+                HTTPMethod::PUT,
+                &[("If-Unmodified-Since", &at(1000))]
+            ),
+            None
+        );
+        assert_eq!(
+            status(
+                &validators,
+                HTTPMethod::PUT,
+                &[("If-Unmodified-Since", &at(999))]
+            ),
+            Some(412)
+        );
+    }
+
+    #[test]
+    fn ignores_if_modified_since_for_unsafe_methods_and_bad_dates() {
+        let validators = validators(Some(TAG));
+
+        assert_eq!(
+            status(
+                &validators,
+                HTTPMethod::PUT,
+                &[("If-Modified-Since", &at(1000))]
+            ),
+            None
+        );
+        assert_eq!(
+            status(
+                &validators,
+                HTTPMethod::GET,
+                &[("If-Modified-Since", "yesterday")]
+            ),
+            None
+        );
+        assert_eq!(
+            status(
+                &validators,
+                HTTPMethod::PUT,
+                &[("If-Unmodified-Since", "yesterday")]
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn if_match_comes_before_if_unmodified_since() {
+        let validators = validators(Some(TAG));
+
+        // -> A matching If-Match makes a stale If-Unmodified-Since irrelevant
+        assert_eq!(
+            status(
+                &validators,
+                HTTPMethod::PUT,
+                &[("If-Match", TAG), ("If-Unmodified-Since", &at(999))]
+            ),
+            None
+        );
+        assert_eq!(
+            status(
+                &validators,
+                HTTPMethod::PUT,
+                &[("If-Match", "\"x\""), ("If-Unmodified-Since", &at(1000))]
+            ),
+            Some(412)
+        );
+    }
+
+    #[test]
+    fn if_unmodified_since_comes_before_if_none_match() {
+        let validators = validators(Some(TAG));
+
+        assert_eq!(
+            status(
+                &validators,
+                HTTPMethod::GET,
+                &[("If-Unmodified-Since", &at(999)), ("If-None-Match", TAG)]
+            ),
+            Some(412)
+        );
+        assert_eq!(
+            status(
+                &validators,
+                HTTPMethod::GET,
+                &[("If-Match", "\"x\""), ("If-None-Match", TAG)]
+            ),
+            Some(412)
+        );
+    }
+
+    #[test]
+    fn if_none_match_comes_before_if_modified_since() {
+        let validators = validators(Some(TAG));
+
+        // -> A changed tag means a full response even if the date says unmodified
+        assert_eq!(
+            status(
+                &validators,
+                HTTPMethod::GET,
+                &[("If-None-Match", "\"x\""), ("If-Modified-Since", &at(1000))]
+            ),
+            None
+        );
+        assert_eq!(
+            status(
+                &validators,
+                HTTPMethod::GET,
+                &[("If-None-Match", TAG), ("If-Modified-Since", &at(999))]
+            ),
+            Some(304)
+        );
+    }
+
+    #[test]
+    fn not_modified_carries_the_validators() {
+        let validators = validators(Some(TAG));
+        let mut headers = HeaderMap::new();
+        headers.append("If-None-Match", TAG);
+
+        let response = validators.evaluate(HTTPMethod::GET, &headers).unwrap();
+        assert_eq!(response.headers.get("ETag"), Some(TAG));
+        assert_eq!(
+            response.headers.get("Last-Modified"),
+            Some(at(1000).as_str())
+        );
+    }
+
+    #[test]
+    fn if_range_needs_a_strong_tag_or_the_exact_date() {
+        let strong = validators(Some(TAG));
+        let weak = validators(Some(WEAK));
+
+        assert!(strong.if_range(TAG));
+        assert!(!strong.if_range("\"x\""));
+        assert!(!strong.if_range(WEAK));
+        assert!(!weak.if_range(WEAK));
+        assert!(!weak.if_range(TAG));
+
+        assert!(strong.if_range(&at(1000)));
+        assert!(!strong.if_range(&at(999)));
+        assert!(!strong.if_range(&at(1001)));
+        assert!(!strong.if_range("yesterday"));
+    }
+
+    #[test]
+    fn builds_tags_from_metadata() {
+        let metadata = std::fs::metadata(file!()).unwrap();
+
+        let strong = Validators::from_metadata(&metadata, EtagMode::Strong);
+        let weak = Validators::from_metadata(&metadata, EtagMode::Weak);
+        let off = Validators::from_metadata(&metadata, EtagMode::Off);
+
+        let tag = strong.etag.unwrap();
+        assert!(tag.starts_with('"') && tag.ends_with('"'));
+        assert_eq!(weak.etag, Some(format!("W/{tag}")));
+        assert_eq!(off.etag, None);
+        assert!(off.last_modified.is_some());
+    }
+}
@@ -0,0 +1,677 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::OnceLock,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    LogLevel,
+    cli::Args,
+    compress,
+    conditional::EtagMode,
+    defaults::{
+        AUTOINDEX, COMPRESSION, COMPRESSION_MIN_SIZE, CONFIG_FILE, HOST, HTTP2, INDEX_FILES,
+        KEEP_ALIVE_TIMEOUT, LOGGING, MAX_BODY_SIZE, MAX_REQUESTS, PORT, PRECOMPRESSED, QUEUE_SIZE,
+        ROOT_FOLDER, SERVE_HIDDEN, THREADS, TLS_RELOAD_INTERVAL,
+    },
+    headers::is_token_char,
+    resolve::SymlinkPolicy,
+    tls::TlsVersion,
+    vhost::HostPattern,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub root: PathBuf,
+    pub index_files: Vec<String>,
+    pub logging: bool,
+    pub log_level: LogLevel,
+    pub hosts: Vec<String>,
+    pub port: u16,
+    // -> "host:port" entries, replace hosts/port when set
+    pub listen: Vec<String>,
+    pub threads: usize,
+    pub queue_size: usize,
+    pub keep_alive_timeout: u64,
+    pub max_requests: usize,
+    pub max_body_size: u64,
+    pub symlinks: SymlinkPolicy,
+    pub serve_hidden: bool,
+    // -> List directories that have no index file
+    pub autoindex: bool,
+    // -> "strong", "weak" or "off"
+    pub etag: EtagMode,
+    // -> TRACE and CONNECT get 501 unless enabled, then they reach the handlers
+    pub enable_trace: bool,
+    pub enable_connect: bool,
+    // -> On-the-fly gzip, deflate and brotli for responses of a compressible type
+    pub compression: bool,
+    pub compression_min_size: u64,
+    // -> "text/*" or exact MIME types
+    pub compression_types: Vec<String>,
+    // -> Serve "file.br" or "file.gz" next to a file when the client accepts it
+    pub precompressed: bool,
+    // -> HTTP/2 via ALPN on HTTPS, prior knowledge or "Upgrade: h2c" on plaintext
+    pub http2: bool,
+    // -> Extension to MIME type, adds to or replaces the built-in table
+    pub mime_types: BTreeMap<String, String>,
+    pub tls: TlsConfig,
+    pub vhosts: Vec<VirtualHostConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    // -> HTTPS listeners, same forms as the top-level listen, none means no HTTPS
+    pub listen: Vec<String>,
+    // -> PEM files, used when SNI matches no vhost certificate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cert: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<PathBuf>,
+    // -> "1.2" and "1.3"
+    pub versions: Vec<TlsVersion>,
+    // -> The plaintext listeners redirect everything to HTTPS
+    pub redirect: bool,
+    // -> Seconds between checks for changed certificate files, 0 never reloads
+    pub reload_interval: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VirtualHostConfig {
+    // -> "example.com", "*.example.com" or "*"
+    pub names: Vec<String>,
+    pub root: PathBuf,
+    // -> Falls back to the top-level index_files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index_files: Option<Vec<String>>,
+    // -> Falls back to the top-level autoindex
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub autoindex: Option<bool>,
+    // -> Added to responses that don't set them
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    // -> PEM files picked by SNI for the names above
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cert: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<PathBuf>,
+    // -> Answers requests no other host matches, instead of the top-level root
+    #[serde(default)]
+    pub default: bool,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            root: PathBuf::from(ROOT_FOLDER),
+            index_files: INDEX_FILES.iter().map(|f| String::from(*f)).collect(),
+            logging: LOGGING,
+            log_level: LogLevel::default(),
+            hosts: vec![String::from(HOST)],
+            port: PORT,
+            listen: Vec::new(),
+            threads: THREADS,
+            queue_size: QUEUE_SIZE,
+            keep_alive_timeout: KEEP_ALIVE_TIMEOUT,
+            max_requests: MAX_REQUESTS,
+            max_body_size: MAX_BODY_SIZE,
+            symlinks: SymlinkPolicy::default(),
+            serve_hidden: SERVE_HIDDEN,
+            autoindex: AUTOINDEX,
+            etag: EtagMode::default(),
+            enable_trace: false,
+            enable_connect: false,
+            compression: COMPRESSION,
+            compression_min_size: COMPRESSION_MIN_SIZE,
+            compression_types: compress::default_types(),
+            precompressed: PRECOMPRESSED,
+            http2: HTTP2,
+            mime_types: BTreeMap::new(),
+            tls: TlsConfig::default(),
+            vhosts: Vec::new(),
+        }
+    }
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        TlsConfig {
+            listen: Vec::new(),
+            cert: None,
+            key: None,
+            versions: vec![TlsVersion::Tls12, TlsVersion::Tls13],
+            redirect: false,
+            reload_interval: TLS_RELOAD_INTERVAL,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    pub key: String,
+    // -> Where the bad value came from, e.g. "server.toml" or "env PORT"
+    pub source: String,
+    pub message: String,
+}
+
+impl ConfigError {
+    fn new(key: &str, source: impl Into<String>, message: impl Into<String>) -> ConfigError {
+        ConfigError {
+            key: String::from(key),
+            source: source.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.key.is_empty() {
+            true => write!(f, "{}: {}", self.source, self.message),
+            false => write!(
+                f,
+                "Invalid `{}` ({}): {}",
+                self.key, self.source, self.message
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl ServerConfig {
+    // -> Defaults < config file < environment < command line
+    pub fn load(args: &Args) -> Result<ServerConfig, ConfigError> {
+        let path = match (&args.config, env::var("CONFIG")) {
+            (Some(path), _) => Some(path.clone()),
+            (None, Ok(path)) => Some(PathBuf::from(path)),
+            (None, Err(_)) => None,
+        };
+
+        let mut config = match path {
+            Some(path) => ServerConfig::from_file(&path)?,
+            None if Path::new(CONFIG_FILE).is_file() => {
+                ServerConfig::from_file(Path::new(CONFIG_FILE))?
+            }
+            None => ServerConfig::default(),
+        };
+
+        config.apply_env()?;
+        config.apply_args(args);
+        config.normalize();
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<ServerConfig, ConfigError> {
+        let source = path.display().to_string();
+
+        let contents = match fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) => return Err(ConfigError::new("", source, e.to_string())),
+        };
+
+        ServerConfig::from_toml(&contents, &source)
+    }
+
+    pub fn from_toml(contents: &str, source: &str) -> Result<ServerConfig, ConfigError> {
+        match toml::from_str::<ServerConfig>(contents) {
+            Ok(config) => Ok(config),
+            Err(e) => {
+                // -> The span points at the offending value, its line names the key
+                let (key, line) = match e.span() {
+                    Some(span) => {
+                        let line = contents[..span.start].matches('\n').count() + 1;
+                        (key_at(contents, line), Some(line))
+                    }
+                    None => (String::new(), None),
+                };
+
+                let source = match line {
+                    Some(l) => format!("{source} line {l}"),
+                    None => String::from(source),
+                };
+
+                Err(ConfigError::new(&key, source, e.message().trim()))
+            }
+        }
+    }
+
+    pub fn apply_env(&mut self) -> Result<(), ConfigError> {
+        env_value("ROOT", &mut self.root)?;
+        env_list("INDEX_FILES", &mut self.index_files);
+        env_value("LOGGING", &mut self.logging)?;
+        env_value("LOG_LEVEL", &mut self.log_level)?;
+        env_value("THREADS", &mut self.threads)?;
+        env_value("QUEUE_SIZE", &mut self.queue_size)?;
+        env_value("KEEP_ALIVE_TIMEOUT", &mut self.keep_alive_timeout)?;
+        env_value("MAX_REQUESTS", &mut self.max_requests)?;
+        env_value("MAX_BODY_SIZE", &mut self.max_body_size)?;
+        env_value("SYMLINKS", &mut self.symlinks)?;
+        env_value("SERVE_HIDDEN", &mut self.serve_hidden)?;
+        env_value("AUTOINDEX", &mut self.autoindex)?;
+        env_value("ETAG", &mut self.etag)?;
+        env_value("ENABLE_TRACE", &mut self.enable_trace)?;
+        env_value("ENABLE_CONNECT", &mut self.enable_connect)?;
+        env_value("COMPRESSION", &mut self.compression)?;
+        env_value("COMPRESSION_MIN_SIZE", &mut self.compression_min_size)?;
+        env_list("COMPRESSION_TYPES", &mut self.compression_types);
+        env_value("PRECOMPRESSED", &mut self.precompressed)?;
+        env_value("HTTP2", &mut self.http2)?;
+
+        // -> A host or port from a higher layer replaces listen entries from a lower one
+        let host_set = env_list("HOST", &mut self.hosts);
+        let port_set = env_value("PORT", &mut self.port)?;
+        if host_set || port_set {
+            self.listen.clear();
+        }
+        env_list("LISTEN", &mut self.listen);
+
+        env_list("TLS_LISTEN", &mut self.tls.listen);
+        env_path("TLS_CERT", &mut self.tls.cert);
+        env_path("TLS_KEY", &mut self.tls.key);
+        env_value("TLS_REDIRECT", &mut self.tls.redirect)?;
+        env_value("TLS_RELOAD_INTERVAL", &mut self.tls.reload_interval)?;
+
+        let mut versions = Vec::new();
+        if env_list("TLS_VERSIONS", &mut versions) {
+            self.tls.versions = Vec::with_capacity(versions.len());
+            for version in versions {
+                match version.parse::<TlsVersion>() {
+                    Ok(v) => self.tls.versions.push(v),
+                    Err(_) => {
+                        return Err(ConfigError::new(
+                            "tls.versions",
+                            "env TLS_VERSIONS",
+                            format!("\"{version}\" is not a TLS version"),
+                        ));
+                    }
+                }
+            }
+        }
+
+        // -> MIME_TYPES="ext=type,ext=type"
+        if let Ok(value) = env::var("MIME_TYPES") {
+            for entry in value.split(',').filter(|e| !e.trim().is_empty()) {
+                match entry.split_once('=') {
+                    Some((ext, mime)) => {
+                        self.mime_types
+                            .insert(String::from(ext.trim()), String::from(mime.trim()));
+                    }
+                    None => {
+                        return Err(ConfigError::new(
+                            "mime_types",
+                            "env MIME_TYPES",
+                            format!("\"{entry}\" is not an ext=type pair"),
+                        ));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn apply_args(&mut self, args: &Args) {
+        if let Some(root) = &args.root {
+            self.root = root.clone();
+        }
+
+        if let Some(level) = args.log_level {
+            self.logging = true;
+            self.log_level = level;
+        }
+
+        if !args.hosts.is_empty() {
+            self.hosts = args.hosts.clone();
+        }
+
+        if let Some(port) = args.port {
+            self.port = port;
+        }
+
+        if !args.hosts.is_empty() || args.port.is_some() {
+            self.listen.clear();
+        }
+
+        if !args.listen.is_empty() {
+            self.listen = args.listen.clone();
+        }
+    }
+
+    fn normalize(&mut self) {
+        self.mime_types = std::mem::take(&mut self.mime_types)
+            .into_iter()
+            .map(|(ext, mime)| (ext.trim_start_matches('.').to_ascii_lowercase(), mime))
+            .collect();
+
+        for mime in &mut self.compression_types {
+            *mime = mime.trim().to_ascii_lowercase();
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |key: &str, message: String| Err(ConfigError::new(key, "config", message));
+
+        if !self.root.is_dir() {
+            return invalid(
+                "root",
+                format!("{} is not a directory", self.root.display()),
+            );
+        }
+
+        validate_index_files("index_files", &self.index_files)?;
+
+        if self.hosts.is_empty() && self.listen.is_empty() {
+            return invalid("hosts", String::from("no address to listen on"));
+        }
+
+        for (key, value) in [
+            ("threads", self.threads as u64),
+            ("queue_size", self.queue_size as u64),
+            ("keep_alive_timeout", self.keep_alive_timeout),
+            ("max_requests", self.max_requests as u64),
+        ] {
+            if value == 0 {
+                return invalid(key, String::from("must be at least 1"));
+            }
+        }
+
+        for (ext, mime) in &self.mime_types {
+            if ext.is_empty() {
+                return invalid("mime_types", String::from("empty extension"));
+            }
+            if !mime.contains('/') {
+                return invalid(
+                    "mime_types",
+                    format!("\"{mime}\" for .{ext} is not a MIME type"),
+                );
+            }
+        }
+
+        for mime in &self.compression_types {
+            if !mime.contains('/') {
+                return invalid(
+                    "compression_types",
+                    format!("\"{mime}\" is not a MIME type"),
+                );
+            }
+        }
+
+        validate_key_pair("tls", &self.tls.cert, &self.tls.key)?;
+
+        if self.tls.versions.is_empty() {
+            return invalid("tls.versions", String::from("needs at least one version"));
+        }
+
+        if self.tls.redirect && self.tls.listen.is_empty() {
+            return invalid(
+                "tls.redirect",
+                String::from("needs at least one tls.listen address"),
+            );
+        }
+
+        let mut defaults = 0;
+
+        for (i, vhost) in self.vhosts.iter().enumerate() {
+            let key = |field: &str| format!("vhosts[{i}].{field}");
+
+            if vhost.names.is_empty() {
+                return invalid(&key("names"), String::from("needs at least one host name"));
+            }
+
+            for name in &vhost.names {
+                if let Err(e) = HostPattern::parse(name) {
+                    return invalid(&key("names"), e);
+                }
+            }
+
+            if !vhost.root.is_dir() {
+                return invalid(
+                    &key("root"),
+                    format!("{} is not a directory", vhost.root.display()),
+                );
+            }
+
+            if let Some(files) = &vhost.index_files {
+                validate_index_files(&key("index_files"), files)?;
+            }
+
+            validate_key_pair(&format!("vhosts[{i}]"), &vhost.cert, &vhost.key)?;
+
+            for name in vhost.headers.keys() {
+                if name.is_empty() || !name.bytes().all(is_token_char) {
+                    return invalid(&key("headers"), format!("\"{name}\" is not a header name"));
+                }
+            }
+
+            if vhost.default {
+                defaults += 1;
+            }
+        }
+
+        if defaults > 1 {
+            return invalid("vhosts", String::from("only one host can be the default"));
+        }
+
+        Ok(())
+    }
+
+    pub fn to_toml(&self) -> Result<String, ConfigError> {
+        match toml::to_string_pretty(self) {
+            Ok(s) => Ok(s),
+            Err(e) => Err(ConfigError::new("", "config", e.to_string())),
+        }
+    }
+}
+
+static CONFIG: OnceLock<ServerConfig> = OnceLock::new();
+
+// -> Makes the loaded config the one the rest of the server reads, first call wins
+pub fn install(config: ServerConfig) -> &'static ServerConfig {
+    CONFIG.get_or_init(|| config)
+}
+
+// -> Falls back to the defaults when nothing was installed, e.g. in tests
+pub fn get() -> &'static ServerConfig {
+    CONFIG.get_or_init(ServerConfig::default)
+}
+
+fn validate_index_files(key: &str, files: &[String]) -> Result<(), ConfigError> {
+    for file in files {
+        if file.is_empty() || file.contains(['/', '\\']) {
+            return Err(ConfigError::new(
+                key,
+                "config",
+                format!("\"{file}\" is not a file name"),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// -> A key as written left of "=", dotted or quoted, as opposed to text inside a value
+fn is_bare_key(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'-' | b'.' | b'"'))
+}
+
+// -> Full path of the key on a TOML line, named the way validate() names it
+fn key_at(contents: &str, line: usize) -> String {
+    let mut table = String::new();
+    let mut arrays: HashMap<String, usize> = HashMap::new();
+    let mut key = String::new();
+
+    for text in contents.lines().take(line) {
+        let text = text.trim();
+
+        if let Some(name) = text.strip_prefix("[[").and_then(|t| t.split_once("]]")) {
+            let name = name.0.trim();
+            let count = arrays.entry(String::from(name)).or_insert(0);
+            table = format!("{name}[{count}]");
+            *count += 1;
+            key = String::new();
+        } else if let Some(name) = text.strip_prefix('[').and_then(|t| t.split_once(']')) {
+            table = String::from(name.0.trim());
+            key = String::new();
+        } else if let Some((name, _)) = text.split_once('=')
+            && is_bare_key(name.trim())
+        {
+            // -> Lines without one continue a multi-line value of the last key
+            key = String::from(name.trim().trim_matches('"'));
+        }
+    }
+
+    match (table.is_empty(), key.is_empty()) {
+        (true, _) => key,
+        (false, true) => table,
+        (false, false) => format!("{table}.{key}"),
+    }
+}
+
+// -> A certificate needs its key and the other way around, both have to be files
+fn validate_key_pair(
+    prefix: &str,
+    cert: &Option<PathBuf>,
+    key: &Option<PathBuf>,
+) -> Result<(), ConfigError> {
+    let paths = match (cert, key) {
+        (None, None) => return Ok(()),
+        (Some(cert), Some(key)) => [("cert", cert), ("key", key)],
+        (Some(_), None) => {
+            return Err(ConfigError::new(
+                &format!("{prefix}.key"),
+                "config",
+                "a certificate needs a key",
+            ));
+        }
+        (None, Some(_)) => {
+            return Err(ConfigError::new(
+                &format!("{prefix}.cert"),
+                "config",
+                "a key needs a certificate",
+            ));
+        }
+    };
+
+    for (field, path) in paths {
+        if !path.is_file() {
+            return Err(ConfigError::new(
+                &format!("{prefix}.{field}"),
+                "config",
+                format!("{} is not a file", path.display()),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn env_path(key: &str, target: &mut Option<PathBuf>) {
+    if let Ok(value) = env::var(key) {
+        *target = Some(PathBuf::from(value.trim()));
+    }
+}
+
+// -> Returns whether the variable was set
+fn env_value<T: FromStr>(key: &str, target: &mut T) -> Result<bool, ConfigError> {
+    let value = match env::var(key) {
+        Ok(v) => v,
+        Err(_) => return Ok(false),
+    };
+
+    match value.trim().parse::<T>() {
+        Ok(parsed) => {
+            *target = parsed;
+            Ok(true)
+        }
+        Err(_) => Err(ConfigError::new(
+            &key.to_ascii_lowercase(),
+            format!("env {key}"),
+            format!("\"{value}\" is not a valid value"),
+        )),
+    }
+}
+
+fn env_list(key: &str, target: &mut Vec<String>) -> bool {
+    match env::var(key) {
+        Ok(value) => {
+            *target = value
+                .split(',')
+                .map(|v| String::from(v.trim()))
+                .filter(|v| !v.is_empty())
+                .collect();
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_key(contents: &str) -> String {
+        match ServerConfig::from_toml(contents, "test.toml") {
+            Ok(_) => panic!("expected an error for {contents:?}"),
+            Err(e) => e.key,
+        }
+    }
+
+    #[test]
+    fn names_top_level_keys() {
+        assert_eq!(error_key("threads = \"many\"\n"), "threads");
+        assert_eq!(error_key("root = \".\"\nlogging = 3\n"), "logging");
+    }
+
+    #[test]
+    fn prefixes_keys_with_their_table() {
+        assert_eq!(error_key("[tls]\nversions = [\"1.4\"]\n"), "tls.versions");
+        assert_eq!(
+            error_key("threads = 2\n\n[tls]\nredirect = \"yes\"\n"),
+            "tls.redirect"
+        );
+    }
+
+    #[test]
+    fn names_keys_inside_multi_line_values() {
+        assert_eq!(
+            error_key("[tls]\nversions = [\n  \"1.2\",\n  \"1.4\",\n]\n"),
+            "tls.versions"
+        );
+    }
+
+    #[test]
+    fn indexes_keys_of_array_tables() {
+        let first = "[[vhosts]]\nnames = [\"a.test\"]\nroot = 5\n";
+        assert_eq!(error_key(first), "vhosts[0].root");
+
+        let second = "[[vhosts]]\nnames = [\"a.test\"]\nroot = \"a\"\n\n\
+                      [[vhosts]]\nnames = [\"b.test\"]\nroot = 5\n";
+        assert_eq!(error_key(second), "vhosts[1].root");
+    }
+
+    #[test]
+    fn names_the_table_for_errors_on_its_header() {
+        assert_eq!(error_key("[[vhosts]]\nnames = [\"a.test\"]\n"), "vhosts[0]");
+    }
+
+    #[test]
+    fn reports_line_of_the_error() {
+        match ServerConfig::from_toml("threads = 2\n[tls]\nversions = [\"1.4\"]\n", "test.toml") {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => assert_eq!(e.source, "test.toml line 3"),
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    time::Duration,
+};
+
+use crate::{
+    HTTPMethod, HTTPRequest, HTTPResponse, LogLevel,
+    body::{Framing, RequestBody},
+    config,
+    handler::Handler,
+    http2::{self, Upgrade},
+    log,
+    parser::{self, ParseError},
+    router,
+    status::{ClientErrorCode, HTTPStatusCode, InformalCode, ServerErrorCode},
+    stream::Stream,
+    vhost,
+};
+
+const CONTINUE: &[u8] = b"HTTP/1.1 100 Continue\r\n\r\n";
+
+pub fn handle_connection(stream: Stream, handler: &dyn Handler) {
+    serve(&stream, handler);
+    stream.close();
+}
+
+fn serve(stream: &Stream, handler: &dyn Handler) {
+    let config = config::get();
+    let timeout = config.keep_alive_timeout;
+    let max_requests = config.max_requests.max(1);
+
+    // -> Idle connections are dropped once the keep-alive timeout passes
+    if stream
+        .set_read_timeout(Some(Duration::from_secs(timeout.max(1))))
+        .is_err()
+    {
+        return;
+    }
+
+    let read_half = match stream.try_clone() {
+        Ok(s) => s,
+        Err(_) => return,
+    };
+
+    let max_body = config.max_body_size;
+    let mut reader: Box<dyn BufRead + Send> = Box::new(BufReader::new(read_half));
+    let mut writer = match stream.try_clone() {
+        Ok(s) => s,
+        Err(_) => return,
+    };
+    let mut served = 0;
+
+    if config.http2 && starts_http2(stream, &mut reader) {
+        return http2::serve(reader, writer, handler, None);
+    }
+
+    loop {
+        // -> Pipelined requests stay buffered in the reader and are answered in order
+        let mut request = match HTTPRequest::from_buf_reader(&mut reader) {
+            Ok(r) => r,
+            Err(ParseError::Closed) => break,
+            Err(e) => return reject(&mut writer, e),
+        };
+
+        // -> RFC 9112 6.1, HTTP/1.0 has no transfer codings to frame a body with
+        if request.version == "1.0" && request.headers.contains("Transfer-Encoding") {
+            return reject(&mut writer, ParseError::UnsupportedTransferEncoding);
+        }
+
+        let framing = match parser::body_framing(&request.headers, max_body) {
+            Ok(f) => f,
+            Err(e) => return reject(&mut writer, e),
+        };
+
+        // -> Only body-less requests are upgraded, the body would have to go out as HTTP/1.1
+        if config.http2
+            && !stream.is_secure()
+            && framing == Framing::Empty
+            && let Some(settings) = h2c_settings(&request)
+        {
+            let mut switching = HTTPResponse::builder()
+                .status(HTTPStatusCode::Informal(InformalCode::SwitchingProtocols))
+                .header("Connection", "Upgrade")
+                .header("Upgrade", "h2c")
+                .build();
+
+            if switching.write_to(&mut writer).is_err() {
+                break;
+            }
+
+            let upgrade = Upgrade { request, settings };
+            return http2::serve(reader, writer, handler, Some(upgrade));
+        }
+
+        if let Some(expect) = request.headers.get("Expect") {
+            if !expect.eq_ignore_ascii_case("100-continue") {
+                return reject(&mut writer, ParseError::ExpectationFailed);
+            }
+
+            // -> Tell the client to go ahead before the handler starts reading, 1.0 clients don't wait for it
+            if framing != Framing::Empty
+                && request.version == "1.1"
+                && writer.write_all(CONTINUE).is_err()
+            {
+                break;
+            }
+        }
+
+        request.body = RequestBody::from_reader(reader, framing, max_body);
+        served += 1;
+
+        let mut response = respond(handler, &mut request);
+        let next_reader = request.body.finish();
+        let body_error = request.body.error();
+        let keep_alive = request.keep_alive() && served < max_requests && next_reader.is_some();
+
+        // -> A body that failed mid-way turns the response into the matching error
+        if let Some(code) = body_error {
+            response = HTTPResponse::builder().status(code).build();
+        }
+
+        if request.method == HTTPMethod::HEAD {
+            response.strip_body();
+        }
+
+        // -> A 1.0 client gets a 1.0 response, framed by Content-Length and never chunked
+        if request.version == "1.0" {
+            response.version = String::from("1.0");
+            response.headers.remove("Transfer-Encoding");
+        }
+
+        if !keep_alive {
+            response.headers.insert("Connection", "close");
+        } else if request.has_connection_token("keep-alive") {
+            response.headers.insert("Connection", "keep-alive");
+            response.headers.insert(
+                "Keep-Alive",
+                format!("timeout={}, max={}", timeout, max_requests - served),
+            );
+        }
+
+        if let Err(e) = response.write_to(&mut writer) {
+            log(LogLevel::Debug, format!("Unable to write response: {}", e));
+            break;
+        }
+
+        reader = match next_reader {
+            Some(r) if keep_alive => r,
+            _ => break,
+        };
+    }
+}
+
+fn reject<W: Write>(writer: &mut W, error: ParseError) {
+    log(LogLevel::Info, format!("Rejecting request: {}", error));
+
+    let mut response = HTTPResponse::builder()
+        .status(error.status())
+        .header("Connection", "close")
+        .build();
+
+    let _ = response.write_to(writer);
+}
+
+fn respond(handler: &dyn Handler, request: &mut HTTPRequest) -> HTTPResponse {
+    match request.version.as_str() {
+        // -> RFC 9112 3.2, exactly one valid Host header
+        "1.1" if !has_valid_host(request) => HTTPResponse::builder()
+            .status(HTTPStatusCode::ClientError(ClientErrorCode::BadRequest))
+            .build(),
+        "1.1" => dispatch(handler, request),
+        // -> HTTP/1.0 predates Host, one that is sent still has to be valid
+        "1.0" if request.headers.contains("Host") && !has_valid_host(request) => {
+            HTTPResponse::builder()
+                .status(HTTPStatusCode::ClientError(ClientErrorCode::BadRequest))
+                .build()
+        }
+        "1.0" => dispatch(handler, request),
+
+        &_ => HTTPResponse::builder()
+            .status(HTTPStatusCode::ServerError(
+                ServerErrorCode::HTTPVersionNotSupported,
+            ))
+            .build(),
+    }
+}
+
+pub(crate) fn dispatch(handler: &dyn Handler, request: &mut HTTPRequest) -> HTTPResponse {
+    let config = config::get();
+
+    match request.method {
+        HTTPMethod::TRACE if !config.enable_trace => not_implemented(),
+        HTTPMethod::CONNECT if !config.enable_connect => not_implemented(),
+        // -> "OPTIONS *" asks about the server as a whole, not about a resource
+        HTTPMethod::OPTIONS if request.path.as_os_str() == "*" => {
+            let mut methods = handler.methods();
+            if config.enable_trace {
+                methods.push(HTTPMethod::TRACE);
+            }
+            if config.enable_connect {
+                methods.push(HTTPMethod::CONNECT);
+            }
+            router::options(&methods)
+        }
+        _ => handler.handle(request),
+    }
+}
+
+fn not_implemented() -> HTTPResponse {
+    HTTPResponse::builder()
+        .status(HTTPStatusCode::ServerError(ServerErrorCode::NotImplemented))
+        .build()
+}
+
+// -> ALPN "h2" on TLS, the client preface on plaintext (prior knowledge)
+fn starts_http2(stream: &Stream, reader: &mut Box<dyn BufRead + Send>) -> bool {
+    if stream.is_secure() {
+        return stream.alpn_protocol().as_deref() == Some(b"h2".as_slice());
+    }
+
+    match reader.fill_buf() {
+        Ok(buffer) => buffer.starts_with(&http2::PREFACE[..16]),
+        Err(_) => false,
+    }
+}
+
+// -> RFC 7540 3.2, the decoded HTTP2-Settings of an "Upgrade: h2c" request
+fn h2c_settings(request: &HTTPRequest) -> Option<Vec<u8>> {
+    if request.version != "1.1"
+        || !request.headers.has_token("Upgrade", "h2c")
+        || !request.has_connection_token("upgrade")
+        || !request.has_connection_token("http2-settings")
+    {
+        return None;
+    }
+
+    match request.headers.get_all("HTTP2-Settings").as_slice() {
+        [value] => http2::decode_settings(value),
+        _ => None,
+    }
+}
+
+fn has_valid_host(request: &HTTPRequest) -> bool {
+    match request.headers.get_all("Host").as_slice() {
+        [host] => vhost::is_valid_host(host.trim()),
+        _ => false,
+    }
+}
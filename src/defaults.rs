@@ -1,3 +1,32 @@
 pub const ROOT_FOLDER: &str = "public";
-pub const INDEX_EXTENSIONS: [&str; 2] = [".php", ".html"];
+pub const INDEX_FILES: [&str; 1] = ["index.html"];
 pub const LOGGING: bool = true;
+pub const THREADS: usize = 4;
+pub const QUEUE_SIZE: usize = 64;
+pub const KEEP_ALIVE_TIMEOUT: u64 = 5;
+pub const MAX_REQUESTS: usize = 100;
+pub const CHUNK_SIZE: usize = 64 * 1024;
+pub const DEFAULT_MIME_TYPE: &str = "application/octet-stream";
+pub const SERVER_NAME: &str = concat!("rust-web-server/", env!("CARGO_PKG_VERSION"));
+pub const MAX_REQUEST_LINE: usize = 8 * 1024;
+pub const MAX_EMPTY_LINES: usize = 4;
+pub const MAX_HEADER_COUNT: usize = 100;
+pub const MAX_HEADER_SIZE: usize = 32 * 1024;
+pub const MAX_BODY_SIZE: u64 = 10 * 1024 * 1024;
+pub const MAX_CHUNK_LINE: usize = 1024;
+pub const MAX_DRAIN_SIZE: u64 = 64 * 1024;
+pub const SERVE_HIDDEN: bool = false;
+pub const HOST: &str = "127.0.0.1";
+pub const PORT: u16 = 7878;
+pub const CONFIG_FILE: &str = "server.toml";
+pub const AUTOINDEX: bool = false;
+pub const NOINDEX_FILE: &str = ".noindex";
+pub const MAX_RANGES: usize = 16;
+pub const COMPRESSION: bool = true;
+pub const COMPRESSION_MIN_SIZE: u64 = 1024;
+pub const MAX_COMPRESS_SIZE: u64 = 10 * 1024 * 1024;
+pub const PRECOMPRESSED: bool = true;
+pub const TLS_PORT: u16 = 443;
+pub const TLS_RELOAD_INTERVAL: u64 = 60;
+pub const HTTP2: bool = true;
+pub const H2_MAX_STREAMS: u32 = 100;
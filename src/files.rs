@@ -0,0 +1,298 @@
+use std::{
+    fs::{File, Metadata},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    HTTPMethod, HTTPRequest, HTTPResponse, LogLevel, RequestURL, autoindex,
+    body::Body,
+    compress::{self, Encoding},
+    conditional::{EtagMode, Validators},
+    config::{self, ServerConfig},
+    defaults::{AUTOINDEX, INDEX_FILES, PRECOMPRESSED},
+    handler::Handler,
+    log, mime,
+    range::{self, RangeRequest},
+    resolve::Resolver,
+    router,
+    status::{ClientErrorCode, HTTPStatusCode, RedirectionCode, ServerErrorCode, SuccessCode},
+};
+
+enum Target {
+    File(PathBuf),
+    // -> Index file of a requested directory
+    Index(PathBuf),
+    // -> Unresolved path below the root of a directory without an index file
+    Directory(PathBuf),
+}
+
+// -> Serves files below one root, each virtual host gets its own
+pub struct Files {
+    resolver: Resolver,
+    index_files: Vec<String>,
+    autoindex: bool,
+    etag: EtagMode,
+    precompressed: bool,
+}
+
+impl Files {
+    pub fn new(resolver: Resolver) -> Files {
+        Files {
+            resolver,
+            index_files: INDEX_FILES.iter().map(|f| String::from(*f)).collect(),
+            autoindex: AUTOINDEX,
+            etag: EtagMode::default(),
+            precompressed: PRECOMPRESSED,
+        }
+    }
+
+    pub fn from_config(config: &ServerConfig) -> Files {
+        Files::new(Resolver::from_config(config))
+            .index_files(config.index_files.clone())
+            .autoindex(config.autoindex)
+            .etag(config.etag)
+            .precompressed(config.precompressed)
+    }
+
+    pub fn index_files(mut self, index_files: Vec<String>) -> Files {
+        self.index_files = index_files;
+        self
+    }
+
+    pub fn autoindex(mut self, autoindex: bool) -> Files {
+        self.autoindex = autoindex;
+        self
+    }
+
+    pub fn etag(mut self, mode: EtagMode) -> Files {
+        self.etag = mode;
+        self
+    }
+
+    pub fn precompressed(mut self, precompressed: bool) -> Files {
+        self.precompressed = precompressed;
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        self.resolver.root()
+    }
+
+    pub fn get_file(&self, url: &RequestURL) -> Result<(Body, String), HTTPStatusCode> {
+        match self.lookup(url)? {
+            Target::File(path) | Target::Index(path) => {
+                let (file, metadata) = open(&path)?;
+                Ok((body(file, &metadata), mime::from_path(&path)))
+            }
+            Target::Directory(_) => Err(HTTPStatusCode::ClientError(ClientErrorCode::NotFound)),
+        }
+    }
+
+    fn lookup(&self, url: &RequestURL) -> Result<Target, HTTPStatusCode> {
+        let candidate = self.resolver.candidate(url)?;
+        let root_path = match self.resolver.check(&candidate) {
+            Ok(p) => p,
+            Err(code) => {
+                log(
+                    LogLevel::Debug,
+                    format!(
+                        "File or Directory \"{}\" can't be served",
+                        candidate.display()
+                    ),
+                );
+                return Err(code);
+            }
+        };
+
+        if !root_path.is_dir() {
+            return Ok(Target::File(root_path));
+        }
+
+        // -> The index file may be a symlink of its own, so it goes through the same check
+        match self.index_path(&candidate) {
+            Some(index) => Ok(Target::Index(self.resolver.check(&index)?)),
+            None => Ok(Target::Directory(candidate)),
+        }
+    }
+
+    fn respond_file(
+        &self,
+        request: &HTTPRequest,
+        path: &Path,
+    ) -> Result<HTTPResponse, HTTPStatusCode> {
+        // -> The type is always the one of the original file, the sibling only changes the coding
+        let content_type = mime::from_path(path);
+        let siblings = self.siblings(path);
+
+        let encoding = compress::negotiate(
+            request.headers.get("Accept-Encoding"),
+            &siblings.iter().map(|(e, _)| *e).collect::<Vec<_>>(),
+        );
+
+        let served = match siblings.iter().find(|(e, _)| Some(*e) == encoding) {
+            Some((_, sibling)) => sibling.as_path(),
+            None => path,
+        };
+
+        let mut response = self.respond_representation(request, served, &content_type)?;
+
+        if let Some(encoding) = encoding
+            && !response.headers.contains("Content-Encoding")
+            && matches!(
+                response.status,
+                HTTPStatusCode::Success(SuccessCode::OK | SuccessCode::PartialContent)
+            )
+        {
+            response
+                .headers
+                .insert("Content-Encoding", encoding.token());
+        }
+
+        if !siblings.is_empty() {
+            compress::add_vary(&mut response.headers, "Accept-Encoding");
+        }
+
+        Ok(response)
+    }
+
+    fn respond_representation(
+        &self,
+        request: &HTTPRequest,
+        path: &Path,
+        content_type: &str,
+    ) -> Result<HTTPResponse, HTTPStatusCode> {
+        let (file, metadata) = open(path)?;
+        let validators = Validators::from_metadata(&metadata, self.etag);
+
+        if let Some(response) = validators.evaluate(request.method, &request.headers) {
+            return Ok(response);
+        }
+
+        let length = metadata.len();
+
+        // -> Ranges only apply to GET, and If-Range falls back to the full file once it changed
+        if request.method == HTTPMethod::GET
+            && let Some(value) = request.headers.get("Range")
+            && request
+                .headers
+                .get("If-Range")
+                .is_none_or(|v| validators.if_range(v))
+        {
+            match range::parse(value, length) {
+                RangeRequest::Unsatisfiable => return Ok(range::not_satisfiable(length)),
+                RangeRequest::Ranges(ranges) => {
+                    let mut response = match range::partial(file, length, content_type, &ranges) {
+                        Ok(r) => r,
+                        Err(_) => return Err(internal_error()),
+                    };
+                    validators.apply(&mut response.headers);
+                    return Ok(response);
+                }
+                RangeRequest::Ignore => (),
+            }
+        }
+
+        let mut response = HTTPResponse::builder()
+            .header("Content-Type", content_type)
+            .header("Accept-Ranges", "bytes")
+            .body(body(file, &metadata))
+            .build();
+        validators.apply(&mut response.headers);
+
+        Ok(response)
+    }
+
+    // -> "style.css.br" and "style.css.gz" next to the file, symlinks could point out of the root
+    fn siblings(&self, path: &Path) -> Vec<(Encoding, PathBuf)> {
+        let name = match (
+            self.precompressed,
+            path.file_name().and_then(|n| n.to_str()),
+        ) {
+            (true, Some(n)) => n,
+            _ => return Vec::new(),
+        };
+
+        Encoding::ALL
+            .iter()
+            .filter_map(|encoding| {
+                let sibling = path.with_file_name(format!("{name}.{}", encoding.extension()?));
+                match std::fs::symlink_metadata(&sibling) {
+                    Ok(m) if m.is_file() => Some((*encoding, sibling)),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    fn index_path(&self, path: &Path) -> Option<PathBuf> {
+        self.index_files
+            .iter()
+            .map(|file| path.join(file))
+            .find(|test_path| test_path.exists())
+    }
+}
+
+impl Handler for Files {
+    fn handle(&self, request: &mut HTTPRequest) -> HTTPResponse {
+        // -> Static files are read-only
+        match request.method {
+            HTTPMethod::GET | HTTPMethod::HEAD => (),
+            HTTPMethod::OPTIONS => return router::options(&[HTTPMethod::GET]),
+            _ => return router::method_not_allowed(&[HTTPMethod::GET]),
+        }
+
+        let url = match request.url() {
+            Ok(u) => u,
+            Err(code) => return HTTPResponse::builder().status(code).build(),
+        };
+
+        let result = match self.lookup(&url) {
+            // -> Relative links in a directory page need the slash form as their base
+            Ok(target @ (Target::Index(_) | Target::Directory(_)))
+                if !url.has_trailing_slash()
+                    && (self.autoindex || matches!(target, Target::Index(_))) =>
+            {
+                let location = match url.query() {
+                    Some(query) => format!("{}/?{}", url.encoded_path(), query),
+                    None => format!("{}/", url.encoded_path()),
+                };
+                Ok(HTTPResponse::redirect(
+                    RedirectionCode::MovedPermanently,
+                    location,
+                ))
+            }
+            Ok(Target::File(path) | Target::Index(path)) => self.respond_file(request, &path),
+            Ok(Target::Directory(dir)) if self.autoindex => {
+                autoindex::render(&self.resolver, &dir, &url, request.headers.get("Accept"))
+            }
+            Ok(Target::Directory(_)) => Err(HTTPStatusCode::ClientError(ClientErrorCode::NotFound)),
+            Err(code) => Err(code),
+        };
+
+        match result {
+            Ok(response) => response,
+            Err(code) => HTTPResponse::builder().status(code).build(),
+        }
+    }
+}
+
+pub fn serve(request: &mut HTTPRequest) -> HTTPResponse {
+    Files::from_config(config::get()).handle(request)
+}
+
+fn open(path: &Path) -> Result<(File, Metadata), HTTPStatusCode> {
+    log(LogLevel::Debug, format!("Getting file: {}", path.display()));
+
+    match File::open(path).and_then(|f| f.metadata().map(|m| (f, m))) {
+        Ok(opened) => Ok(opened),
+        Err(_) => Err(internal_error()),
+    }
+}
+
+fn internal_error() -> HTTPStatusCode {
+    HTTPStatusCode::ServerError(ServerErrorCode::InternalServerError)
+}
+
+fn body(file: File, metadata: &Metadata) -> Body {
+    Body::File(file, metadata.len())
+}
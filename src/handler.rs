@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use crate::{HTTPMethod, HTTPRequest, HTTPResponse};
+
+pub trait Handler: Send + Sync {
+    fn handle(&self, request: &mut HTTPRequest) -> HTTPResponse;
+
+    // -> What "OPTIONS *" advertises, handlers that don't say are taken to be read-only
+    fn methods(&self) -> Vec<HTTPMethod> {
+        vec![HTTPMethod::GET]
+    }
+}
+
+impl<F> Handler for F
+where
+    F: Fn(&mut HTTPRequest) -> HTTPResponse + Send + Sync,
+{
+    fn handle(&self, request: &mut HTTPRequest) -> HTTPResponse {
+        self(request)
+    }
+}
+
+impl<H: Handler + ?Sized> Handler for Arc<H> {
+    fn handle(&self, request: &mut HTTPRequest) -> HTTPResponse {
+        (**self).handle(request)
+    }
+
+    fn methods(&self) -> Vec<HTTPMethod> {
+        (**self).methods()
+    }
+}
+
+// -> Runs around a handler, may change the request, the response, or answer on its own
+pub trait Middleware: Send + Sync {
+    fn handle(&self, request: &mut HTTPRequest, next: &dyn Handler) -> HTTPResponse;
+}
+
+impl<F> Middleware for F
+where
+    F: Fn(&mut HTTPRequest, &dyn Handler) -> HTTPResponse + Send + Sync,
+{
+    fn handle(&self, request: &mut HTTPRequest, next: &dyn Handler) -> HTTPResponse {
+        self(request, next)
+    }
+}
+
+pub struct Stack {
+    middleware: Vec<Arc<dyn Middleware>>,
+    handler: Arc<dyn Handler>,
+}
+
+impl Stack {
+    pub fn new<H: Handler + 'static>(handler: H) -> Stack {
+        Stack {
+            middleware: Vec::new(),
+            handler: Arc::new(handler),
+        }
+    }
+
+    // -> The first middleware added is the outermost one
+    pub fn wrap<M: Middleware + 'static>(mut self, middleware: M) -> Stack {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+}
+
+impl Handler for Stack {
+    fn handle(&self, request: &mut HTTPRequest) -> HTTPResponse {
+        Next {
+            middleware: &self.middleware,
+            handler: self.handler.as_ref(),
+        }
+        .handle(request)
+    }
+
+    fn methods(&self) -> Vec<HTTPMethod> {
+        self.handler.methods()
+    }
+}
+
+// -> Adds the methods that aren't listed yet, keeping the order
+pub fn merge_methods(methods: &mut Vec<HTTPMethod>, more: Vec<HTTPMethod>) {
+    for method in more {
+        if !methods.contains(&method) {
+            methods.push(method);
+        }
+    }
+}
+
+struct Next<'a> {
+    middleware: &'a [Arc<dyn Middleware>],
+    handler: &'a dyn Handler,
+}
+
+impl Handler for Next<'_> {
+    fn handle(&self, request: &mut HTTPRequest) -> HTTPResponse {
+        match self.middleware.split_first() {
+            Some((current, rest)) => current.handle(
+                request,
+                &Next {
+                    middleware: rest,
+                    handler: self.handler,
+                },
+            ),
+            None => self.handler.handle(request),
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use std::{fmt, io};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeaderMap {
+    // -> Kept as a list so insertion order and repeated names survive
+    entries: Vec<(String, String)>,
+}
+
+impl HeaderMap {
+    pub fn new() -> HeaderMap {
+        HeaderMap {
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn get_all(&self, name: &str) -> Vec<&str> {
+        self.entries
+            .iter()
+            .filter(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    pub fn has_token(&self, name: &str, token: &str) -> bool {
+        self.get_all(name)
+            .iter()
+            .flat_map(|value| value.split(','))
+            .any(|t| t.trim().eq_ignore_ascii_case(token))
+    }
+
+    pub fn append(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.entries.push((name.into(), value.into()));
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        let name = name.into();
+        let value = value.into();
+
+        // -> Replace the first occurrence in place and drop the rest
+        match self
+            .entries
+            .iter()
+            .position(|(key, _)| key.eq_ignore_ascii_case(&name))
+        {
+            Some(index) => {
+                let mut position = 0;
+                self.entries.retain(|(key, _)| {
+                    position += 1;
+                    position <= index + 1 || !key.eq_ignore_ascii_case(&name)
+                });
+                self.entries[index] = (name, value);
+            }
+            None => self.entries.push((name, value)),
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        let first = self.get(name).map(String::from);
+        self.entries
+            .retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+        first
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn validate(&self) -> io::Result<()> {
+        for (key, value) in &self.entries {
+            if !is_valid_name(key) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid header name \"{key}\""),
+                ));
+            }
+
+            // -> CR/LF in a value would let it inject headers or a body
+            if value.bytes().any(|b| b == b'\r' || b == b'\n' || b == 0) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid value for header \"{key}\""),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for HeaderMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (key, value) in &self.entries {
+            write!(f, "{key}: {value}\r\n")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for HeaderMap {
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        let mut map = HeaderMap::new();
+
+        for (key, value) in iter {
+            map.append(key, value);
+        }
+
+        map
+    }
+}
+
+pub fn is_token_char(b: u8) -> bool {
+    // https://www.rfc-editor.org/rfc/rfc9110#name-tokens
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(is_token_char)
+}
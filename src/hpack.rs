@@ -0,0 +1,750 @@
+use std::{collections::HashMap, collections::VecDeque, sync::OnceLock};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HpackError {
+    // -> A COMPRESSION_ERROR for the whole connection
+    Malformed,
+    // -> The decoded list went past the limit, the table is still in sync
+    TooLarge,
+}
+
+type Field = (Vec<u8>, Vec<u8>);
+
+// https://www.rfc-editor.org/rfc/rfc7541#appendix-A
+const STATIC_TABLE: [(&str, &str); 61] = [
+    (":authority", ""),
+    (":method", "GET"),
+    (":method", "POST"),
+    (":path", "/"),
+    (":path", "/index.html"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "200"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "304"),
+    (":status", "400"),
+    (":status", "404"),
+    (":status", "500"),
+    ("accept-charset", ""),
+    ("accept-encoding", "gzip, deflate"),
+    ("accept-language", ""),
+    ("accept-ranges", ""),
+    ("accept", ""),
+    ("access-control-allow-origin", ""),
+    ("age", ""),
+    ("allow", ""),
+    ("authorization", ""),
+    ("cache-control", ""),
+    ("content-disposition", ""),
+    ("content-encoding", ""),
+    ("content-language", ""),
+    ("content-length", ""),
+    ("content-location", ""),
+    ("content-range", ""),
+    ("content-type", ""),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("expect", ""),
+    ("expires", ""),
+    ("from", ""),
+    ("host", ""),
+    ("if-match", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("if-range", ""),
+    ("if-unmodified-since", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("max-forwards", ""),
+    ("proxy-authenticate", ""),
+    ("proxy-authorization", ""),
+    ("range", ""),
+    ("referer", ""),
+    ("refresh", ""),
+    ("retry-after", ""),
+    ("server", ""),
+    ("set-cookie", ""),
+    ("strict-transport-security", ""),
+    ("transfer-encoding", ""),
+    ("user-agent", ""),
+    ("vary", ""),
+    ("via", ""),
+    ("www-authenticate", ""),
+];
+
+// https://www.rfc-editor.org/rfc/rfc7541#appendix-B, (code, bits) by symbol, 256 is EOS
+const HUFFMAN: [(u32, u8); 257] = [
+    (0x1ff8, 13),
+    (0x7fffd8, 23),
+    (0xfffffe2, 28),
+    (0xfffffe3, 28),
+    (0xfffffe4, 28),
+    (0xfffffe5, 28),
+    (0xfffffe6, 28),
+    (0xfffffe7, 28),
+    (0xfffffe8, 28),
+    (0xffffea, 24),
+    (0x3ffffffc, 30),
+    (0xfffffe9, 28),
+    (0xfffffea, 28),
+    (0x3ffffffd, 30),
+    (0xfffffeb, 28),
+    (0xfffffec, 28),
+    (0xfffffed, 28),
+    (0xfffffee, 28),
+    (0xfffffef, 28),
+    (0xffffff0, 28),
+    (0xffffff1, 28),
+    (0xffffff2, 28),
+    (0x3ffffffe, 30),
+    (0xffffff3, 28),
+    (0xffffff4, 28),
+    (0xffffff5, 28),
+    (0xffffff6, 28),
+    (0xffffff7, 28),
+    (0xffffff8, 28),
+    (0xffffff9, 28),
+    (0xffffffa, 28),
+    (0xffffffb, 28),
+    (0x14, 6),
+    (0x3f8, 10),
+    (0x3f9, 10),
+    (0xffa, 12),
+    (0x1ff9, 13),
+    (0x15, 6),
+    (0xf8, 8),
+    (0x7fa, 11),
+    (0x3fa, 10),
+    (0x3fb, 10),
+    (0xf9, 8),
+    (0x7fb, 11),
+    (0xfa, 8),
+    (0x16, 6),
+    (0x17, 6),
+    (0x18, 6),
+    (0x0, 5),
+    (0x1, 5),
+    (0x2, 5),
+    (0x19, 6),
+    (0x1a, 6),
+    (0x1b, 6),
+    (0x1c, 6),
+    (0x1d, 6),
+    (0x1e, 6),
+    (0x1f, 6),
+    (0x5c, 7),
+    (0xfb, 8),
+    (0x7ffc, 15),
+    (0x20, 6),
+    (0xffb, 12),
+    (0x3fc, 10),
+    (0x1ffa, 13),
+    (0x21, 6),
+    (0x5d, 7),
+    (0x5e, 7),
+    (0x5f, 7),
+    (0x60, 7),
+    (0x61, 7),
+    (0x62, 7),
+    (0x63, 7),
+    (0x64, 7),
+    (0x65, 7),
+    (0x66, 7),
+    (0x67, 7),
+    (0x68, 7),
+    (0x69, 7),
+    (0x6a, 7),
+    (0x6b, 7),
+    (0x6c, 7),
+    (0x6d, 7),
+    (0x6e, 7),
+    (0x6f, 7),
+    (0x70, 7),
+    (0x71, 7),
+    (0x72, 7),
+    (0xfc, 8),
+    (0x73, 7),
+    (0xfd, 8),
+    (0x1ffb, 13),
+    (0x7fff0, 19),
+    (0x1ffc, 13),
+    (0x3ffc, 14),
+    (0x22, 6),
+    (0x7ffd, 15),
+    (0x3, 5),
+    (0x23, 6),
+    (0x4, 5),
+    (0x24, 6),
+    (0x5, 5),
+    (0x25, 6),
+    (0x26, 6),
+    (0x27, 6),
+    (0x6, 5),
+    (0x74, 7),
+    (0x75, 7),
+    (0x28, 6),
+    (0x29, 6),
+    (0x2a, 6),
+    (0x7, 5),
+    (0x2b, 6),
+    (0x76, 7),
+    (0x2c, 6),
+    (0x8, 5),
+    (0x9, 5),
+    (0x2d, 6),
+    (0x77, 7),
+    (0x78, 7),
+    (0x79, 7),
+    (0x7a, 7),
+    (0x7b, 7),
+    (0x7ffe, 15),
+    (0x7fc, 11),
+    (0x3ffd, 14),
+    (0x1ffd, 13),
+    (0xffffffc, 28),
+    (0xfffe6, 20),
+    (0x3fffd2, 22),
+    (0xfffe7, 20),
+    (0xfffe8, 20),
+    (0x3fffd3, 22),
+    (0x3fffd4, 22),
+    (0x3fffd5, 22),
+    (0x7fffd9, 23),
+    (0x3fffd6, 22),
+    (0x7fffda, 23),
+    (0x7fffdb, 23),
+    (0x7fffdc, 23),
+    (0x7fffdd, 23),
+    (0x7fffde, 23),
+    (0xffffeb, 24),
+    (0x7fffdf, 23),
+    (0xffffec, 24),
+    (0xffffed, 24),
+    (0x3fffd7, 22),
+    (0x7fffe0, 23),
+    (0xffffee, 24),
+    (0x7fffe1, 23),
+    (0x7fffe2, 23),
+    (0x7fffe3, 23),
+    (0x7fffe4, 23),
+    (0x1fffdc, 21),
+    (0x3fffd8, 22),
+    (0x7fffe5, 23),
+    (0x3fffd9, 22),
+    (0x7fffe6, 23),
+    (0x7fffe7, 23),
+    (0xffffef, 24),
+    (0x3fffda, 22),
+    (0x1fffdd, 21),
+    (0xfffe9, 20),
+    (0x3fffdb, 22),
+    (0x3fffdc, 22),
+    (0x7fffe8, 23),
+    (0x7fffe9, 23),
+    (0x1fffde, 21),
+    (0x7fffea, 23),
+    (0x3fffdd, 22),
+    (0x3fffde, 22),
+    (0xfffff0, 24),
+    (0x1fffdf, 21),
+    (0x3fffdf, 22),
+    (0x7fffeb, 23),
+    (0x7fffec, 23),
+    (0x1fffe0, 21),
+    (0x1fffe1, 21),
+    (0x3fffe0, 22),
+    (0x1fffe2, 21),
+    (0x7fffed, 23),
+    (0x3fffe1, 22),
+    (0x7fffee, 23),
+    (0x7fffef, 23),
+    (0xfffea, 20),
+    (0x3fffe2, 22),
+    (0x3fffe3, 22),
+    (0x3fffe4, 22),
+    (0x7ffff0, 23),
+    (0x3fffe5, 22),
+    (0x3fffe6, 22),
+    (0x7ffff1, 23),
+    (0x3ffffe0, 26),
+    (0x3ffffe1, 26),
+    (0xfffeb, 20),
+    (0x7fff1, 19),
+    (0x3fffe7, 22),
+    (0x7ffff2, 23),
+    (0x3fffe8, 22),
+    (0x1ffffec, 25),
+    (0x3ffffe2, 26),
+    (0x3ffffe3, 26),
+    (0x3ffffe4, 26),
+    (0x7ffffde, 27),
+    (0x7ffffdf, 27),
+    (0x3ffffe5, 26),
+    (0xfffff1, 24),
+    (0x1ffffed, 25),
+    (0x7fff2, 19),
+    (0x1fffe3, 21),
+    (0x3ffffe6, 26),
+    (0x7ffffe0, 27),
+    (0x7ffffe1, 27),
+    (0x3ffffe7, 26),
+    (0x7ffffe2, 27),
+    (0xfffff2, 24),
+    (0x1fffe4, 21),
+    (0x1fffe5, 21),
+    (0x3ffffe8, 26),
+    (0x3ffffe9, 26),
+    (0xffffffd, 28),
+    (0x7ffffe3, 27),
+    (0x7ffffe4, 27),
+    (0x7ffffe5, 27),
+    (0xfffec, 20),
+    (0xfffff3, 24),
+    (0xfffed, 20),
+    (0x1fffe6, 21),
+    (0x3fffe9, 22),
+    (0x1fffe7, 21),
+    (0x1fffe8, 21),
+    (0x7ffff3, 23),
+    (0x3fffea, 22),
+    (0x3fffeb, 22),
+    (0x1ffffee, 25),
+    (0x1ffffef, 25),
+    (0xfffff4, 24),
+    (0xfffff5, 24),
+    (0x3ffffea, 26),
+    (0x7ffff4, 23),
+    (0x3ffffeb, 26),
+    (0x7ffffe6, 27),
+    (0x3ffffec, 26),
+    (0x3ffffed, 26),
+    (0x7ffffe7, 27),
+    (0x7ffffe8, 27),
+    (0x7ffffe9, 27),
+    (0x7ffffea, 27),
+    (0x7ffffeb, 27),
+    (0xffffffe, 28),
+    (0x7ffffec, 27),
+    (0x7ffffed, 27),
+    (0x7ffffee, 27),
+    (0x7ffffef, 27),
+    (0x7fffff0, 27),
+    (0x3ffffee, 26),
+    (0x3fffffff, 30),
+];
+
+// -> Every table entry costs its name and value plus 32 bytes of overhead
+const ENTRY_OVERHEAD: usize = 32;
+
+pub struct Decoder {
+    table: VecDeque<Field>,
+    size: usize,
+    max_size: usize,
+    // -> SETTINGS_HEADER_TABLE_SIZE we announced, size updates may not go above it
+    limit: usize,
+}
+
+impl Decoder {
+    pub fn new(limit: usize) -> Decoder {
+        Decoder {
+            table: VecDeque::new(),
+            size: 0,
+            max_size: limit,
+            limit,
+        }
+    }
+
+    // -> `max_list` counts name, value and 32 bytes per field, RFC 7541 4.1
+    pub fn decode(&mut self, block: &[u8], max_list: usize) -> Result<Vec<Field>, HpackError> {
+        let mut fields = Vec::new();
+        let mut list = 0;
+        let mut pos = 0;
+
+        while pos < block.len() {
+            let byte = block[pos];
+
+            let field = if byte & 0x80 != 0 {
+                // -> Indexed field
+                let index = integer(block, &mut pos, 7)?;
+                self.entry(index)?
+            } else if byte & 0x40 != 0 {
+                // -> Literal with incremental indexing
+                let field = self.literal(block, &mut pos, 6)?;
+                self.insert(field.clone());
+                field
+            } else if byte & 0x20 != 0 {
+                // -> Dynamic table size update, only before the first field
+                let size = integer(block, &mut pos, 5)?;
+                if size > self.limit || list > 0 {
+                    return Err(HpackError::Malformed);
+                }
+                self.max_size = size;
+                self.evict(0);
+                continue;
+            } else {
+                // -> Literal without indexing or never indexed, both leave the table alone
+                self.literal(block, &mut pos, 4)?
+            };
+
+            // -> Past the limit the rest is still decoded to keep the table in sync, but not kept
+            list += field.0.len() + field.1.len() + ENTRY_OVERHEAD;
+            match list > max_list {
+                true => fields = Vec::new(),
+                false => fields.push(field),
+            }
+        }
+
+        match list > max_list {
+            true => Err(HpackError::TooLarge),
+            false => Ok(fields),
+        }
+    }
+
+    fn literal(&self, block: &[u8], pos: &mut usize, prefix: u8) -> Result<Field, HpackError> {
+        let index = integer(block, pos, prefix)?;
+
+        let name = match index {
+            0 => string(block, pos)?,
+            i => self.entry(i)?.0,
+        };
+
+        Ok((name, string(block, pos)?))
+    }
+
+    fn entry(&self, index: usize) -> Result<Field, HpackError> {
+        match index {
+            0 => Err(HpackError::Malformed),
+            i if i <= STATIC_TABLE.len() => {
+                let (name, value) = STATIC_TABLE[i - 1];
+                Ok((name.as_bytes().to_vec(), value.as_bytes().to_vec()))
+            }
+            i => match self.table.get(i - STATIC_TABLE.len() - 1) {
+                Some(field) => Ok(field.clone()),
+                None => Err(HpackError::Malformed),
+            },
+        }
+    }
+
+    fn insert(&mut self, field: Field) {
+        let size = field.0.len() + field.1.len() + ENTRY_OVERHEAD;
+
+        // -> An entry larger than the table just empties it
+        self.evict(size);
+        if size <= self.max_size {
+            self.size += size;
+            self.table.push_front(field);
+        }
+    }
+
+    // -> Drops the oldest entries until `room` more bytes fit
+    fn evict(&mut self, room: usize) {
+        while self.size + room > self.max_size {
+            match self.table.pop_back() {
+                Some((name, value)) => self.size -= name.len() + value.len() + ENTRY_OVERHEAD,
+                None => break,
+            }
+        }
+    }
+}
+
+// -> Fields as literals without indexing, so the peer's table size never matters
+pub fn encode<'a>(fields: impl IntoIterator<Item = (&'a str, &'a str)>) -> Vec<u8> {
+    let mut out = Vec::new();
+
+    for (name, value) in fields {
+        let indexed = STATIC_TABLE
+            .iter()
+            .position(|(n, v)| *n == name && *v == value && !v.is_empty());
+
+        match indexed {
+            Some(i) => push_integer(&mut out, 0x80, 7, i + 1),
+            None => {
+                match STATIC_TABLE.iter().position(|(n, _)| *n == name) {
+                    Some(i) => push_integer(&mut out, 0x00, 4, i + 1),
+                    None => {
+                        out.push(0x00);
+                        push_string(&mut out, name.as_bytes());
+                    }
+                }
+                push_string(&mut out, value.as_bytes());
+            }
+        }
+    }
+
+    out
+}
+
+// https://www.rfc-editor.org/rfc/rfc7541#section-5.1
+fn integer(input: &[u8], pos: &mut usize, prefix: u8) -> Result<usize, HpackError> {
+    let mask = ((1u16 << prefix) - 1) as u8;
+    let first = match input.get(*pos) {
+        Some(b) => b & mask,
+        None => return Err(HpackError::Malformed),
+    };
+    *pos += 1;
+
+    if first < mask {
+        return Ok(first as usize);
+    }
+
+    let mut value = mask as usize;
+    let mut shift = 0;
+
+    loop {
+        let byte = match input.get(*pos) {
+            Some(b) => *b,
+            None => return Err(HpackError::Malformed),
+        };
+        *pos += 1;
+
+        // -> Anything past 2^28 is far beyond every limit we announce
+        if shift > 21 {
+            return Err(HpackError::Malformed);
+        }
+
+        value += ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+}
+
+fn push_integer(out: &mut Vec<u8>, flags: u8, prefix: u8, value: usize) {
+    let mask = (1usize << prefix) - 1;
+
+    if value < mask {
+        out.push(flags | value as u8);
+        return;
+    }
+
+    out.push(flags | mask as u8);
+    let mut rest = value - mask;
+    while rest >= 0x80 {
+        out.push((rest & 0x7f) as u8 | 0x80);
+        rest >>= 7;
+    }
+    out.push(rest as u8);
+}
+
+fn string(input: &[u8], pos: &mut usize) -> Result<Vec<u8>, HpackError> {
+    let huffman = match input.get(*pos) {
+        Some(b) => b & 0x80 != 0,
+        None => return Err(HpackError::Malformed),
+    };
+    let length = integer(input, pos, 7)?;
+
+    let raw = match input.get(*pos..*pos + length) {
+        Some(r) => r,
+        None => return Err(HpackError::Malformed),
+    };
+    *pos += length;
+
+    match huffman {
+        true => huffman_decode(raw),
+        false => Ok(raw.to_vec()),
+    }
+}
+
+fn push_string(out: &mut Vec<u8>, value: &[u8]) {
+    push_integer(out, 0x00, 7, value.len());
+    out.extend_from_slice(value);
+}
+
+fn huffman_decode(input: &[u8]) -> Result<Vec<u8>, HpackError> {
+    static CODES: OnceLock<HashMap<(u32, u8), u16>> = OnceLock::new();
+    let codes = CODES.get_or_init(|| {
+        HUFFMAN
+            .iter()
+            .enumerate()
+            .map(|(symbol, (code, bits))| ((*code, *bits), symbol as u16))
+            .collect()
+    });
+
+    let mut out = Vec::with_capacity(input.len() * 8 / 5);
+    let mut code: u32 = 0;
+    let mut bits: u8 = 0;
+
+    for byte in input {
+        for shift in (0..8).rev() {
+            code = (code << 1) | ((byte >> shift) & 1) as u32;
+            bits += 1;
+
+            // -> The shortest code has 5 bits, the longest 30
+            if bits < 5 {
+                continue;
+            }
+
+            match codes.get(&(code, bits)) {
+                Some(256) => return Err(HpackError::Malformed),
+                Some(symbol) => {
+                    out.push(*symbol as u8);
+                    code = 0;
+                    bits = 0;
+                }
+                None if bits >= 30 => return Err(HpackError::Malformed),
+                None => (),
+            }
+        }
+    }
+
+    // -> Padding is a prefix of EOS, so at most 7 one bits
+    if bits > 7 || code != (1 << bits) - 1 {
+        return Err(HpackError::Malformed);
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(input: &str) -> Vec<u8> {
+        let digits: Vec<u8> = input.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+        digits
+            .chunks(2)
+            .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).unwrap(), 16).unwrap())
+            .collect()
+    }
+
+    fn fields(pairs: &[(&str, &str)]) -> Vec<Field> {
+        pairs
+            .iter()
+            .map(|(n, v)| (n.as_bytes().to_vec(), v.as_bytes().to_vec()))
+            .collect()
+    }
+
+    // https://www.rfc-editor.org/rfc/rfc7541#appendix-C.3 and C.4 decode to the same lists
+    fn request_vectors(blocks: [&str; 3]) {
+        let mut decoder = Decoder::new(4096);
+
+        let first = decoder.decode(&hex(blocks[0]), usize::MAX).unwrap();
+        assert_eq!(
+            first,
+            fields(&[
+                (":method", "GET"),
+                (":scheme", "http"),
+                (":path", "/"),
+                (":authority", "www.example.com"),
+            ])
+        );
+        assert_eq!(decoder.size, 57);
+
+        let second = decoder.decode(&hex(blocks[1]), usize::MAX).unwrap();
+        assert_eq!(
+            second,
+            fields(&[
+                (":method", "GET"),
+                (":scheme", "http"),
+                (":path", "/"),
+                (":authority", "www.example.com"),
+                ("cache-control", "no-cache"),
+            ])
+        );
+        assert_eq!(decoder.size, 110);
+
+        let third = decoder.decode(&hex(blocks[2]), usize::MAX).unwrap();
+        assert_eq!(
+            third,
+            fields(&[
+                (":method", "GET"),
+                (":scheme", "https"),
+                (":path", "/index.html"),
+                (":authority", "www.example.com"),
+                ("custom-key", "custom-value"),
+            ])
+        );
+        assert_eq!(decoder.size, 164);
+        assert_eq!(
+            decoder.table[0],
+            fields(&[("custom-key", "custom-value")])[0]
+        );
+    }
+
+    #[test]
+    fn decodes_requests_without_huffman() {
+        request_vectors([
+            "8286 8441 0f77 7777 2e65 7861 6d70 6c65 2e63 6f6d",
+            "8286 84be 5808 6e6f 2d63 6163 6865",
+            "8287 85bf 400a 6375 7374 6f6d 2d6b 6579 0c63 7573 746f 6d2d 7661 6c75 65",
+        ]);
+    }
+
+    #[test]
+    fn decodes_requests_with_huffman() {
+        request_vectors([
+            "8286 8441 8cf1 e3c2 e5f2 3a6b a0ab 90f4 ff",
+            "8286 84be 5886 a8eb 1064 9cbf",
+            "8287 85bf 4088 25a8 49e9 5ba9 7d7f 8925 a849 e95b b8e8 b4bf",
+        ]);
+    }
+
+    #[test]
+    fn refuses_table_size_update_above_limit() {
+        let mut decoder = Decoder::new(4096);
+        assert_eq!(
+            decoder.decode(&[0x3f, 0xe1, 0x1f], usize::MAX),
+            Ok(Vec::new())
+        );
+        assert_eq!(
+            decoder.decode(&[0x3f, 0xe2, 0x1f], usize::MAX),
+            Err(HpackError::Malformed)
+        );
+    }
+
+    #[test]
+    fn refuses_table_size_update_after_a_field() {
+        let mut decoder = Decoder::new(4096);
+        assert_eq!(
+            decoder.decode(&[0x82, 0x20], usize::MAX),
+            Err(HpackError::Malformed)
+        );
+    }
+
+    #[test]
+    fn refuses_invalid_huffman_padding() {
+        // -> "0" is 00000, padded with ones it decodes, padded with zeros it doesn't
+        assert_eq!(huffman_decode(&[0x07]), Ok(b"0".to_vec()));
+        assert_eq!(huffman_decode(&[0x00]), Err(HpackError::Malformed));
+        // -> A whole byte of padding is more than the 7 bits allowed
+        assert_eq!(huffman_decode(&[0x07, 0xff]), Err(HpackError::Malformed));
+        // -> EOS itself may never be decoded
+        assert_eq!(
+            huffman_decode(&[0xff, 0xff, 0xff, 0xfc]),
+            Err(HpackError::Malformed)
+        );
+    }
+
+    #[test]
+    fn stops_collecting_past_the_list_limit() {
+        let mut decoder = Decoder::new(4096);
+        let block = hex("8286 8441 0f77 7777 2e65 7861 6d70 6c65 2e63 6f6d");
+
+        assert_eq!(decoder.decode(&block, 100), Err(HpackError::TooLarge));
+        // -> The literal still went into the table, so the next block refers to it correctly
+        assert_eq!(decoder.size, 57);
+        assert_eq!(
+            decoder.decode(&[0xbe], 100),
+            Ok(fields(&[(":authority", "www.example.com")]))
+        );
+    }
+
+    #[test]
+    fn encoded_fields_decode_again() {
+        let list = [
+            (":status", "200"),
+            ("content-type", "text/html"),
+            ("x-custom", "value"),
+        ];
+        let block = encode(list);
+
+        let mut decoder = Decoder::new(4096);
+        assert_eq!(decoder.decode(&block, usize::MAX), Ok(fields(&list)));
+        assert_eq!(decoder.size, 0);
+    }
+}
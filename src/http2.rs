@@ -0,0 +1,1066 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{self, BufRead, ErrorKind, Read, Write},
+};
+
+use crate::{
+    HTTPMethod, HTTPRequest, HTTPResponse, LogLevel,
+    body::RequestBody,
+    config, connection,
+    defaults::{H2_MAX_STREAMS, MAX_HEADER_SIZE},
+    handler::Handler,
+    headers::{HeaderMap, is_token_char},
+    hpack::{self, Decoder, HpackError},
+    log, parser,
+    status::{ClientErrorCode, HTTPStatusCode, ServerErrorCode},
+    stream::Stream,
+};
+
+// https://www.rfc-editor.org/rfc/rfc9113#section-3.4
+pub const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+const DATA: u8 = 0x0;
+const HEADERS: u8 = 0x1;
+const PRIORITY: u8 = 0x2;
+const RST_STREAM: u8 = 0x3;
+const SETTINGS: u8 = 0x4;
+const PUSH_PROMISE: u8 = 0x5;
+const PING: u8 = 0x6;
+const GOAWAY: u8 = 0x7;
+const WINDOW_UPDATE: u8 = 0x8;
+const CONTINUATION: u8 = 0x9;
+
+const END_STREAM: u8 = 0x1;
+const ACK: u8 = 0x1;
+const END_HEADERS: u8 = 0x4;
+const PADDED: u8 = 0x8;
+const PRIORITY_FLAG: u8 = 0x20;
+
+const SETTINGS_HEADER_TABLE_SIZE: u16 = 0x1;
+const SETTINGS_ENABLE_PUSH: u16 = 0x2;
+const SETTINGS_MAX_CONCURRENT_STREAMS: u16 = 0x3;
+const SETTINGS_INITIAL_WINDOW_SIZE: u16 = 0x4;
+const SETTINGS_MAX_FRAME_SIZE: u16 = 0x5;
+const SETTINGS_MAX_HEADER_LIST_SIZE: u16 = 0x6;
+
+const HEADER_TABLE_SIZE: usize = 4096;
+const DEFAULT_WINDOW: i64 = 65_535;
+const MAX_WINDOW: i64 = (1 << 31) - 1;
+const MIN_FRAME_SIZE: usize = 16_384;
+const MAX_FRAME_SIZE: usize = (1 << 24) - 1;
+
+// -> Hop-by-hop fields have no meaning in HTTP/2, a request carrying them is malformed
+const CONNECTION_HEADERS: [&str; 5] = [
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "transfer-encoding",
+    "upgrade",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    NoError = 0x0,
+    ProtocolError = 0x1,
+    InternalError = 0x2,
+    FlowControlError = 0x3,
+    StreamClosed = 0x5,
+    FrameSizeError = 0x6,
+    RefusedStream = 0x7,
+    CompressionError = 0x9,
+}
+
+// -> Ends the whole connection, stream errors are answered with RST_STREAM on the spot
+enum Failure {
+    Connection(ErrorCode),
+    Closed,
+}
+
+impl From<io::Error> for Failure {
+    fn from(_: io::Error) -> Failure {
+        Failure::Closed
+    }
+}
+
+struct Frame {
+    kind: u8,
+    flags: u8,
+    stream: u32,
+    payload: Vec<u8>,
+}
+
+type Field = (Vec<u8>, Vec<u8>);
+
+struct StreamState {
+    fields: Vec<Field>,
+    body: Vec<u8>,
+    // -> What we may still send on this stream
+    window: i64,
+    // -> What the client may still send on this stream
+    recv_window: i64,
+    // -> END_STREAM arrived, the request is complete
+    ended: bool,
+    // -> Answered with this error without waiting for the request, any body is dropped
+    rejected: Option<ClientErrorCode>,
+    // -> Answered before the body ended, the rest is read and dropped
+    answered: bool,
+    // -> Request of an h2c upgrade, already parsed as HTTP/1.1
+    upgraded: Option<HTTPRequest>,
+}
+
+impl StreamState {
+    fn new(fields: Vec<Field>, window: i64, ended: bool) -> StreamState {
+        StreamState {
+            fields,
+            body: Vec::new(),
+            window,
+            recv_window: DEFAULT_WINDOW,
+            ended,
+            rejected: None,
+            answered: false,
+            upgraded: None,
+        }
+    }
+}
+
+// -> The request that asked for "Upgrade: h2c", answered on stream 1
+pub struct Upgrade {
+    pub request: HTTPRequest,
+    // -> Decoded HTTP2-Settings header, a SETTINGS payload
+    pub settings: Vec<u8>,
+}
+
+// -> Requests are answered one after another, frames of other streams are read in between
+struct Connection<'a> {
+    reader: Box<dyn BufRead + Send>,
+    writer: Stream,
+    handler: &'a dyn Handler,
+    decoder: Decoder,
+    streams: HashMap<u32, StreamState>,
+    ready: VecDeque<u32>,
+    // -> Header block spread over CONTINUATION frames: stream, HEADERS flags, fragments so far
+    continuation: Option<(u32, u8, Vec<u8>)>,
+    last_stream: u32,
+    // -> The one stream whose receive window is refilled, the others stop at the initial window
+    uploading: Option<u32>,
+    send_window: i64,
+    initial_window: i64,
+    max_frame: usize,
+    max_body: u64,
+    max_requests: usize,
+    served: usize,
+    going_away: bool,
+    // -> A connection error hit while a response body was being sent
+    failure: Option<ErrorCode>,
+}
+
+pub fn serve(
+    reader: Box<dyn BufRead + Send>,
+    writer: Stream,
+    handler: &dyn Handler,
+    upgrade: Option<Upgrade>,
+) {
+    let config = config::get();
+
+    let mut connection = Connection {
+        reader,
+        writer,
+        handler,
+        decoder: Decoder::new(HEADER_TABLE_SIZE),
+        streams: HashMap::new(),
+        ready: VecDeque::new(),
+        continuation: None,
+        last_stream: 0,
+        uploading: None,
+        send_window: DEFAULT_WINDOW,
+        initial_window: DEFAULT_WINDOW,
+        max_frame: MIN_FRAME_SIZE,
+        max_body: config.max_body_size,
+        max_requests: config.max_requests.max(1),
+        served: 0,
+        going_away: false,
+        failure: None,
+    };
+
+    let result = connection.run(upgrade);
+
+    match result {
+        Err(Failure::Connection(code)) => {
+            log(
+                LogLevel::Debug,
+                format!("Closing HTTP/2 connection: {:?}", code),
+            );
+            let _ = connection.goaway(code);
+        }
+        Err(Failure::Closed) | Ok(()) => {
+            let _ = connection.goaway(ErrorCode::NoError);
+        }
+    }
+}
+
+impl Connection<'_> {
+    fn run(&mut self, upgrade: Option<Upgrade>) -> Result<(), Failure> {
+        // -> The server preface is a SETTINGS frame, sent before reading anything
+        let mut settings = Vec::new();
+        for (id, value) in [
+            (SETTINGS_MAX_CONCURRENT_STREAMS, H2_MAX_STREAMS),
+            (SETTINGS_ENABLE_PUSH, 0),
+            (SETTINGS_MAX_HEADER_LIST_SIZE, MAX_HEADER_SIZE as u32),
+        ] {
+            settings.extend_from_slice(&id.to_be_bytes());
+            settings.extend_from_slice(&value.to_be_bytes());
+        }
+        self.write_frame(SETTINGS, 0, 0, &settings)?;
+
+        if let Some(upgrade) = upgrade {
+            self.apply_settings(&upgrade.settings)?;
+
+            // -> RFC 9113 3.1 removed the upgrade, RFC 7540 3.2 puts its request on stream 1
+            let mut state = StreamState::new(Vec::new(), self.initial_window, true);
+            state.upgraded = Some(upgrade.request);
+            self.streams.insert(1, state);
+            self.ready.push_back(1);
+            self.last_stream = 1;
+        }
+
+        let mut preface = [0; 24];
+        self.reader.read_exact(&mut preface)?;
+        if preface != PREFACE {
+            return Err(Failure::Connection(ErrorCode::ProtocolError));
+        }
+
+        // -> The client preface continues with a SETTINGS frame
+        let first = self.read_frame()?;
+        if first.kind != SETTINGS || first.flags & ACK != 0 {
+            return Err(Failure::Connection(ErrorCode::ProtocolError));
+        }
+        self.process(first)?;
+
+        loop {
+            if let Some(id) = self.ready.pop_front() {
+                self.answer(id)?;
+                continue;
+            }
+
+            if self.going_away && self.streams.is_empty() {
+                return Ok(());
+            }
+
+            let frame = self.read_frame()?;
+            self.process(frame)?;
+        }
+    }
+
+    fn read_frame(&mut self) -> Result<Frame, Failure> {
+        let mut head = [0; 9];
+        self.reader.read_exact(&mut head)?;
+
+        let length = u32::from_be_bytes([0, head[0], head[1], head[2]]) as usize;
+        let stream = u32::from_be_bytes([head[5], head[6], head[7], head[8]]) & 0x7fff_ffff;
+
+        // -> We never raise SETTINGS_MAX_FRAME_SIZE above the default
+        if length > MIN_FRAME_SIZE {
+            return Err(Failure::Connection(ErrorCode::FrameSizeError));
+        }
+
+        let mut payload = vec![0; length];
+        self.reader.read_exact(&mut payload)?;
+
+        Ok(Frame {
+            kind: head[3],
+            flags: head[4],
+            stream,
+            payload,
+        })
+    }
+
+    fn write_frame(&mut self, kind: u8, flags: u8, stream: u32, payload: &[u8]) -> io::Result<()> {
+        let length = (payload.len() as u32).to_be_bytes();
+        let mut frame = Vec::with_capacity(9 + payload.len());
+        frame.extend_from_slice(&length[1..]);
+        frame.push(kind);
+        frame.push(flags);
+        frame.extend_from_slice(&stream.to_be_bytes());
+        frame.extend_from_slice(payload);
+
+        self.writer.write_all(&frame)?;
+        self.writer.flush()
+    }
+
+    fn goaway(&mut self, code: ErrorCode) -> io::Result<()> {
+        let mut payload = Vec::with_capacity(8);
+        payload.extend_from_slice(&self.last_stream.to_be_bytes());
+        payload.extend_from_slice(&(code as u32).to_be_bytes());
+        self.write_frame(GOAWAY, 0, 0, &payload)
+    }
+
+    fn reset(&mut self, stream: u32, code: ErrorCode) -> Result<(), Failure> {
+        self.streams.remove(&stream);
+        self.ready.retain(|id| *id != stream);
+        self.write_frame(RST_STREAM, 0, stream, &(code as u32).to_be_bytes())?;
+        self.release(stream)
+    }
+
+    // -> Hands the refills on to the oldest body still waiting for its window
+    fn release(&mut self, stream: u32) -> Result<(), Failure> {
+        if self.uploading != Some(stream) {
+            return Ok(());
+        }
+
+        let next = self
+            .streams
+            .iter_mut()
+            .filter(|(_, s)| !s.ended && s.rejected.is_none() && s.recv_window < DEFAULT_WINDOW)
+            .min_by_key(|(id, _)| **id);
+
+        let (id, increment) = match next {
+            Some((id, state)) => {
+                let increment = DEFAULT_WINDOW - state.recv_window;
+                state.recv_window = DEFAULT_WINDOW;
+                (*id, increment as u32)
+            }
+            None => {
+                self.uploading = None;
+                return Ok(());
+            }
+        };
+
+        self.uploading = Some(id);
+        self.write_frame(WINDOW_UPDATE, 0, id, &increment.to_be_bytes())?;
+        Ok(())
+    }
+
+    fn process(&mut self, frame: Frame) -> Result<(), Failure> {
+        // -> A header block has to be finished before any other frame
+        if let Some((stream, _, _)) = &self.continuation
+            && (frame.kind != CONTINUATION || frame.stream != *stream)
+        {
+            return Err(Failure::Connection(ErrorCode::ProtocolError));
+        }
+
+        let connection_frame = matches!(frame.kind, SETTINGS | PING | GOAWAY);
+        let stream_frame = matches!(
+            frame.kind,
+            DATA | HEADERS | PRIORITY | RST_STREAM | PUSH_PROMISE | CONTINUATION
+        );
+
+        if (connection_frame && frame.stream != 0) || (stream_frame && frame.stream == 0) {
+            return Err(Failure::Connection(ErrorCode::ProtocolError));
+        }
+
+        match frame.kind {
+            DATA => self.on_data(frame),
+            HEADERS => self.on_headers(frame),
+            PRIORITY => match frame.payload.len() {
+                5 => Ok(()),
+                _ => self.reset(frame.stream, ErrorCode::FrameSizeError),
+            },
+            RST_STREAM => {
+                if frame.payload.len() != 4 {
+                    return Err(Failure::Connection(ErrorCode::FrameSizeError));
+                }
+                if frame.stream > self.last_stream {
+                    return Err(Failure::Connection(ErrorCode::ProtocolError));
+                }
+                self.streams.remove(&frame.stream);
+                self.ready.retain(|id| *id != frame.stream);
+                self.release(frame.stream)
+            }
+            SETTINGS => self.on_settings(frame),
+            PING => {
+                if frame.payload.len() != 8 {
+                    return Err(Failure::Connection(ErrorCode::FrameSizeError));
+                }
+                if frame.flags & ACK == 0 {
+                    self.write_frame(PING, ACK, 0, &frame.payload)?;
+                }
+                Ok(())
+            }
+            GOAWAY => {
+                // -> Streams already started still get their answers
+                self.going_away = true;
+                Ok(())
+            }
+            WINDOW_UPDATE => self.on_window_update(frame),
+            CONTINUATION => {
+                let (stream, flags, mut block) = match self.continuation.take() {
+                    Some(c) => c,
+                    None => return Err(Failure::Connection(ErrorCode::ProtocolError)),
+                };
+
+                block.extend_from_slice(&frame.payload);
+                if block.len() > MAX_HEADER_SIZE * 2 {
+                    return Err(Failure::Connection(ErrorCode::ProtocolError));
+                }
+
+                match frame.flags & END_HEADERS != 0 {
+                    true => self.on_header_block(stream, flags, &block),
+                    false => {
+                        self.continuation = Some((stream, flags, block));
+                        Ok(())
+                    }
+                }
+            }
+            // -> Clients never push
+            PUSH_PROMISE => Err(Failure::Connection(ErrorCode::ProtocolError)),
+            // -> Unknown frame types are ignored, RFC 9113 5.5
+            _ => Ok(()),
+        }
+    }
+
+    fn on_settings(&mut self, frame: Frame) -> Result<(), Failure> {
+        if frame.flags & ACK != 0 {
+            return match frame.payload.is_empty() {
+                true => Ok(()),
+                false => Err(Failure::Connection(ErrorCode::FrameSizeError)),
+            };
+        }
+
+        self.apply_settings(&frame.payload)?;
+        self.write_frame(SETTINGS, ACK, 0, &[])?;
+        Ok(())
+    }
+
+    fn apply_settings(&mut self, payload: &[u8]) -> Result<(), Failure> {
+        if !payload.len().is_multiple_of(6) {
+            return Err(Failure::Connection(ErrorCode::FrameSizeError));
+        }
+
+        for setting in payload.chunks(6) {
+            let id = u16::from_be_bytes([setting[0], setting[1]]);
+            let value = u32::from_be_bytes([setting[2], setting[3], setting[4], setting[5]]);
+
+            match id {
+                SETTINGS_ENABLE_PUSH if value > 1 => {
+                    return Err(Failure::Connection(ErrorCode::ProtocolError));
+                }
+                SETTINGS_INITIAL_WINDOW_SIZE => {
+                    let value = value as i64;
+                    if value > MAX_WINDOW {
+                        return Err(Failure::Connection(ErrorCode::FlowControlError));
+                    }
+
+                    // -> The change applies to every open stream, windows may go negative
+                    let delta = value - self.initial_window;
+                    for state in self.streams.values_mut() {
+                        state.window += delta;
+                        if state.window > MAX_WINDOW {
+                            return Err(Failure::Connection(ErrorCode::FlowControlError));
+                        }
+                    }
+                    self.initial_window = value;
+                }
+                SETTINGS_MAX_FRAME_SIZE => {
+                    let value = value as usize;
+                    if !(MIN_FRAME_SIZE..=MAX_FRAME_SIZE).contains(&value) {
+                        return Err(Failure::Connection(ErrorCode::ProtocolError));
+                    }
+                    self.max_frame = value;
+                }
+                // -> The encoder never uses the dynamic table, so its size doesn't matter
+                SETTINGS_HEADER_TABLE_SIZE | SETTINGS_MAX_CONCURRENT_STREAMS => (),
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn on_window_update(&mut self, frame: Frame) -> Result<(), Failure> {
+        if frame.payload.len() != 4 {
+            return Err(Failure::Connection(ErrorCode::FrameSizeError));
+        }
+
+        let increment = (u32::from_be_bytes([
+            frame.payload[0],
+            frame.payload[1],
+            frame.payload[2],
+            frame.payload[3],
+        ]) & 0x7fff_ffff) as i64;
+
+        if frame.stream == 0 {
+            if increment == 0 {
+                return Err(Failure::Connection(ErrorCode::ProtocolError));
+            }
+            self.send_window += increment;
+            if self.send_window > MAX_WINDOW {
+                return Err(Failure::Connection(ErrorCode::FlowControlError));
+            }
+            return Ok(());
+        }
+
+        let window = match self.streams.get_mut(&frame.stream) {
+            Some(state) => {
+                state.window += increment;
+                state.window
+            }
+            None => return Ok(()),
+        };
+
+        match (increment, window > MAX_WINDOW) {
+            (0, _) => self.reset(frame.stream, ErrorCode::ProtocolError),
+            (_, true) => self.reset(frame.stream, ErrorCode::FlowControlError),
+            _ => Ok(()),
+        }
+    }
+
+    fn on_headers(&mut self, frame: Frame) -> Result<(), Failure> {
+        let mut fragment = unpad(&frame)?;
+
+        if frame.flags & PRIORITY_FLAG != 0 {
+            fragment = match fragment.get(5..) {
+                Some(f) => f,
+                None => return Err(Failure::Connection(ErrorCode::FrameSizeError)),
+            };
+        }
+
+        match frame.flags & END_HEADERS != 0 {
+            true => self.on_header_block(frame.stream, frame.flags, fragment),
+            false => {
+                self.continuation = Some((frame.stream, frame.flags, fragment.to_vec()));
+                Ok(())
+            }
+        }
+    }
+
+    fn on_header_block(&mut self, id: u32, flags: u8, block: &[u8]) -> Result<(), Failure> {
+        // -> Decoded even for refused streams, the table state is shared by the connection
+        let (fields, rejected) = match self.decoder.decode(block, MAX_HEADER_SIZE) {
+            Ok(f) => (f, None),
+            Err(HpackError::TooLarge) => (
+                Vec::new(),
+                Some(ClientErrorCode::RequestHeaderFieldsTooLarge),
+            ),
+            Err(HpackError::Malformed) => {
+                return Err(Failure::Connection(ErrorCode::CompressionError));
+            }
+        };
+        let ended = flags & END_STREAM != 0;
+
+        // -> A second header block on a stream is the trailer section
+        if let Some(state) = self.streams.get_mut(&id) {
+            if state.ended {
+                return self.reset(id, ErrorCode::StreamClosed);
+            }
+            if !ended {
+                return self.reset(id, ErrorCode::ProtocolError);
+            }
+            state.ended = true;
+            self.release(id)?;
+            return self.complete(id);
+        }
+
+        // -> Client streams are odd and always increase
+        if id.is_multiple_of(2) || id <= self.last_stream {
+            return Err(Failure::Connection(ErrorCode::ProtocolError));
+        }
+        self.last_stream = id;
+
+        if self.going_away || self.streams.len() >= H2_MAX_STREAMS as usize {
+            return self.reset(id, ErrorCode::RefusedStream);
+        }
+
+        let mut state = StreamState::new(fields, self.initial_window, ended);
+
+        // -> Too large headers are answered right away, the request is never looked at
+        if rejected.is_some() {
+            state.rejected = rejected;
+            self.streams.insert(id, state);
+            self.ready.push_back(id);
+            return Ok(());
+        }
+
+        self.streams.insert(id, state);
+
+        match ended {
+            true => self.complete(id),
+            false => Ok(()),
+        }
+    }
+
+    fn on_data(&mut self, frame: Frame) -> Result<(), Failure> {
+        let data = unpad(&frame)?;
+        let counted = frame.payload.len() as u32;
+
+        // -> The connection window is handed right back, the stream windows bound what is buffered
+        if counted > 0 {
+            self.write_frame(WINDOW_UPDATE, 0, 0, &counted.to_be_bytes())?;
+        }
+
+        let max_body = self.max_body;
+        let state = match self.streams.get_mut(&frame.stream) {
+            Some(s) => s,
+            None if frame.stream > self.last_stream => {
+                return Err(Failure::Connection(ErrorCode::ProtocolError));
+            }
+            // -> Streams we already answered or reset may still have frames in flight
+            None => return Ok(()),
+        };
+
+        if state.ended {
+            return self.reset(frame.stream, ErrorCode::StreamClosed);
+        }
+
+        state.recv_window -= counted as i64;
+        if state.recv_window < 0 {
+            return self.reset(frame.stream, ErrorCode::FlowControlError);
+        }
+
+        if state.rejected.is_none() {
+            state.body.extend_from_slice(data);
+        }
+
+        let ended = frame.flags & END_STREAM != 0;
+        state.ended = ended;
+
+        // -> Too large, answer now instead of waiting for the rest
+        if state.body.len() as u64 > max_body && state.rejected.is_none() {
+            state.rejected = Some(ClientErrorCode::ContentTooLarge);
+            state.body = Vec::new();
+            self.ready.push_back(frame.stream);
+        }
+
+        // -> Dropped bytes cost nothing, otherwise only one body at a time grows past the
+        // initial window, so a connection buffers at most max_body plus a window per stream
+        let dropped = state.rejected.is_some();
+        let refill = !ended
+            && counted > 0
+            && (dropped || self.uploading.is_none_or(|id| id == frame.stream));
+
+        if refill {
+            state.recv_window += counted as i64;
+            if !dropped {
+                self.uploading = Some(frame.stream);
+            }
+            self.write_frame(WINDOW_UPDATE, 0, frame.stream, &counted.to_be_bytes())?;
+        }
+
+        if ended || dropped {
+            self.release(frame.stream)?;
+        }
+
+        match ended {
+            true => self.complete(frame.stream),
+            false => Ok(()),
+        }
+    }
+
+    // -> The request is complete once END_STREAM arrived
+    fn complete(&mut self, id: u32) -> Result<(), Failure> {
+        let state = match self.streams.get(&id) {
+            Some(s) => s,
+            None => return Ok(()),
+        };
+
+        if state.answered {
+            self.streams.remove(&id);
+            return Ok(());
+        }
+
+        if state.rejected.is_some() {
+            return Ok(());
+        }
+
+        // -> A declared length has to match what was sent, RFC 9113 8.1.1
+        let declared = state
+            .fields
+            .iter()
+            .find(|(name, _)| name == b"content-length")
+            .map(|(_, value)| String::from_utf8_lossy(value).trim().parse::<u64>());
+
+        match declared {
+            Some(Ok(length)) if length == state.body.len() as u64 => (),
+            Some(_) => return self.reset(id, ErrorCode::ProtocolError),
+            None => (),
+        }
+
+        self.ready.push_back(id);
+        Ok(())
+    }
+
+    fn answer(&mut self, id: u32) -> Result<(), Failure> {
+        let state = match self.streams.get_mut(&id) {
+            Some(s) => s,
+            None => return Ok(()),
+        };
+
+        let prepared = match (state.upgraded.take(), state.rejected) {
+            (Some(request), _) => Ok(request),
+            (None, Some(code)) => Err(Some(HTTPStatusCode::ClientError(code))),
+            (None, None) => request(
+                std::mem::take(&mut state.fields),
+                std::mem::take(&mut state.body),
+            ),
+        };
+
+        let mut response = match prepared {
+            Ok(mut request) => {
+                let mut response = connection::dispatch(self.handler, &mut request);
+                if request.method == HTTPMethod::HEAD {
+                    response.strip_body();
+                }
+                response
+            }
+            Err(Some(code)) => HTTPResponse::builder().status(code).build(),
+            Err(None) => return self.reset(id, ErrorCode::ProtocolError),
+        };
+
+        self.served += 1;
+        self.respond(id, &mut response)?;
+
+        // -> Resetting a stream the client is still sending on makes some clients drop the response
+        match self.streams.get_mut(&id) {
+            Some(state) if !state.ended => state.answered = true,
+            _ => {
+                self.streams.remove(&id);
+            }
+        }
+
+        if self.served >= self.max_requests && !self.going_away {
+            self.going_away = true;
+            self.goaway(ErrorCode::NoError)?;
+        }
+
+        Ok(())
+    }
+
+    fn respond(&mut self, id: u32, response: &mut HTTPResponse) -> Result<(), Failure> {
+        response.complete_headers();
+
+        // -> RFC 9113 8.2.1, the same values HTTP/1.1 refuses to put on the wire
+        if let Err(e) = response.headers.validate() {
+            log(LogLevel::Debug, format!("Unable to write response: {}", e));
+            return self.reset(id, ErrorCode::InternalError);
+        }
+
+        let status = response.status.to_value().to_string();
+        let mut fields: Vec<(String, &str)> = vec![(String::from(":status"), status.as_str())];
+
+        for (name, value) in response.headers.iter() {
+            let name = name.to_ascii_lowercase();
+            if !CONNECTION_HEADERS.contains(&name.as_str()) {
+                fields.push((name, value));
+            }
+        }
+
+        let block = hpack::encode(fields.iter().map(|(n, v)| (n.as_str(), *v)));
+
+        let bodiless = matches!(response.status.to_value(), 100..=199 | 204 | 304);
+        let mut body = match response.contents.take() {
+            Some(b) if !bodiless && !b.is_empty() => Some(b),
+            _ => None,
+        };
+
+        self.write_headers(id, &block, body.is_none())?;
+
+        if let Some(body) = &mut body {
+            let mut writer = BodyWriter {
+                connection: self,
+                stream: id,
+                buffer: Vec::new(),
+            };
+
+            let result = body.write_to(&mut writer).and_then(|_| writer.finish());
+
+            if result.is_err() {
+                return match self.failure.take() {
+                    Some(code) => Err(Failure::Connection(code)),
+                    None if self.streams.contains_key(&id) => {
+                        self.reset(id, ErrorCode::InternalError)
+                    }
+                    // -> Reset by the client while we were sending
+                    None => Ok(()),
+                };
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_headers(&mut self, id: u32, block: &[u8], end_stream: bool) -> io::Result<()> {
+        let mut chunks = block.chunks(self.max_frame).peekable();
+        let mut first = true;
+
+        // -> An empty block still needs its HEADERS frame
+        if block.is_empty() {
+            let flags = END_HEADERS | if end_stream { END_STREAM } else { 0 };
+            return self.write_frame(HEADERS, flags, id, &[]);
+        }
+
+        while let Some(chunk) = chunks.next() {
+            let mut flags = 0;
+            if chunks.peek().is_none() {
+                flags |= END_HEADERS;
+            }
+
+            match first {
+                true => {
+                    if end_stream {
+                        flags |= END_STREAM;
+                    }
+                    self.write_frame(HEADERS, flags, id, chunk)?;
+                }
+                false => self.write_frame(CONTINUATION, flags, id, chunk)?,
+            }
+            first = false;
+        }
+
+        Ok(())
+    }
+
+    // -> Sends DATA within both flow control windows, reading frames while they are exhausted
+    fn send_data(&mut self, id: u32, mut data: &[u8], end_stream: bool) -> io::Result<()> {
+        loop {
+            let window = match self.streams.get(&id) {
+                Some(state) => state.window.min(self.send_window),
+                None => return Err(io::Error::other("stream reset by the client")),
+            };
+
+            if data.is_empty() {
+                let flags = if end_stream { END_STREAM } else { 0 };
+                return match end_stream {
+                    true => self.write_frame(DATA, flags, id, &[]),
+                    false => Ok(()),
+                };
+            }
+
+            if window <= 0 {
+                let frame = match self.read_frame() {
+                    Ok(f) => f,
+                    Err(_) => return Err(io::Error::from(ErrorKind::ConnectionAborted)),
+                };
+
+                if let Err(Failure::Connection(code)) = self.process(frame) {
+                    self.failure = Some(code);
+                    return Err(io::Error::other("connection error"));
+                }
+                continue;
+            }
+
+            let size = data.len().min(window as usize).min(self.max_frame);
+            let last = size == data.len();
+            let flags = if last && end_stream { END_STREAM } else { 0 };
+
+            self.write_frame(DATA, flags, id, &data[..size])?;
+
+            self.send_window -= size as i64;
+            if let Some(state) = self.streams.get_mut(&id) {
+                state.window -= size as i64;
+            }
+
+            data = &data[size..];
+            if last {
+                return Ok(());
+            }
+        }
+    }
+}
+
+// -> Cuts a response body into DATA frames
+struct BodyWriter<'c, 'a> {
+    connection: &'c mut Connection<'a>,
+    stream: u32,
+    buffer: Vec<u8>,
+}
+
+impl BodyWriter<'_, '_> {
+    fn finish(&mut self) -> io::Result<()> {
+        let buffer = std::mem::take(&mut self.buffer);
+        self.connection.send_data(self.stream, &buffer, true)
+    }
+}
+
+impl Write for BodyWriter<'_, '_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+
+        let size = self.connection.max_frame;
+        if self.buffer.len() >= size {
+            let full = self.buffer.len() - self.buffer.len() % size;
+            let rest = self.buffer.split_off(full);
+            let chunk = std::mem::replace(&mut self.buffer, rest);
+            self.connection.send_data(self.stream, &chunk, false)?;
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn unpad(frame: &Frame) -> Result<&[u8], Failure> {
+    if frame.flags & PADDED == 0 {
+        return Ok(&frame.payload);
+    }
+
+    let padding = match frame.payload.first() {
+        Some(p) => *p as usize,
+        None => return Err(Failure::Connection(ErrorCode::FrameSizeError)),
+    };
+
+    match frame.payload.len().checked_sub(padding + 1) {
+        Some(length) => Ok(&frame.payload[1..1 + length]),
+        None => Err(Failure::Connection(ErrorCode::ProtocolError)),
+    }
+}
+
+// -> Builds the request a handler sees, Err(None) means malformed, RFC 9113 8.1.1
+fn request(fields: Vec<Field>, body: Vec<u8>) -> Result<HTTPRequest, Option<HTTPStatusCode>> {
+    let mut method = None;
+    let mut scheme = None;
+    let mut authority = None;
+    let mut path = None;
+    let mut headers = HeaderMap::new();
+    let mut cookies: Vec<String> = Vec::new();
+    let mut regular = false;
+    let mut size = 0;
+
+    for (name, value) in fields {
+        size += name.len() + value.len() + 32;
+
+        let (name, value) = match (String::from_utf8(name), String::from_utf8(value)) {
+            (Ok(n), Ok(v)) => (n, v),
+            _ => return Err(None),
+        };
+
+        if value.bytes().any(|b| b == b'\r' || b == b'\n' || b == 0) {
+            return Err(None);
+        }
+
+        if let Some(pseudo) = name.strip_prefix(':') {
+            // -> Pseudo-headers come first and only once
+            let slot = match pseudo {
+                "method" => &mut method,
+                "scheme" => &mut scheme,
+                "authority" => &mut authority,
+                "path" => &mut path,
+                _ => return Err(None),
+            };
+            if regular || slot.is_some() {
+                return Err(None);
+            }
+            *slot = Some(value);
+            continue;
+        }
+
+        regular = true;
+
+        if name.is_empty()
+            || !name.bytes().all(is_token_char)
+            || name.bytes().any(|b| b.is_ascii_uppercase())
+            || CONNECTION_HEADERS.contains(&name.as_str())
+            || (name == "te" && value != "trailers")
+        {
+            return Err(None);
+        }
+
+        // -> Cookies may be split into several fields, handlers expect one
+        match name.as_str() {
+            "cookie" => cookies.push(value),
+            _ => headers.append(name, value),
+        }
+    }
+
+    if size > MAX_HEADER_SIZE {
+        return Err(Some(HTTPStatusCode::ClientError(
+            ClientErrorCode::RequestHeaderFieldsTooLarge,
+        )));
+    }
+
+    if !cookies.is_empty() {
+        headers.insert("cookie", cookies.join("; "));
+    }
+
+    let method = match method.map(|m| m.parse::<HTTPMethod>()) {
+        Some(Ok(m)) => m,
+        Some(Err(_)) => {
+            return Err(Some(HTTPStatusCode::ServerError(
+                ServerErrorCode::NotImplemented,
+            )));
+        }
+        None => return Err(None),
+    };
+
+    // -> CONNECT names only the authority, everything else needs a scheme and a path
+    let target = match (method, scheme, path, &authority) {
+        (HTTPMethod::CONNECT, None, None, Some(authority)) => authority.clone(),
+        (HTTPMethod::CONNECT, _, _, _) => return Err(None),
+        (_, Some(_), Some(path), _) if !path.is_empty() => path,
+        _ => return Err(None),
+    };
+
+    let path = match parser::parse_target(&method, &target) {
+        Ok(p) => p,
+        Err(_) => return Err(None),
+    };
+
+    if let Some(authority) = authority
+        && !headers.contains("host")
+    {
+        headers.insert("host", authority);
+    }
+
+    Ok(HTTPRequest {
+        method,
+        path,
+        version: String::from("2"),
+        headers,
+        params: HashMap::new(),
+        body: RequestBody::from_bytes(body),
+    })
+}
+
+// -> HTTP2-Settings is base64url without padding
+pub fn decode_settings(value: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(value.len() * 3 / 4);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for byte in value.trim().trim_end_matches('=').bytes() {
+        let sextet = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'-' => 62,
+            b'_' => 63,
+            _ => return None,
+        };
+
+        buffer = (buffer << 6) | sextet as u32;
+        bits += 6;
+
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_http2_settings() {
+        // -> SETTINGS_MAX_CONCURRENT_STREAMS 100, INITIAL_WINDOW_SIZE 10 MiB, ENABLE_PUSH 0
+        assert_eq!(
+            decode_settings("AAMAAABkAAQAoAAAAAIAAAAA"),
+            Some(vec![
+                0, 3, 0, 0, 0, 100, 0, 4, 0, 160, 0, 0, 0, 2, 0, 0, 0, 0
+            ])
+        );
+        assert_eq!(decode_settings(""), Some(Vec::new()));
+        assert_eq!(decode_settings("AA=="), Some(vec![0]));
+        assert_eq!(decode_settings("-_8"), Some(vec![0xfb, 0xff]));
+    }
+
+    #[test]
+    fn refuses_plain_base64_in_http2_settings() {
+        assert_eq!(decode_settings("AA+A"), None);
+        assert_eq!(decode_settings("AA/A"), None);
+    }
+}
@@ -1,22 +1,52 @@
+pub mod autoindex;
+pub mod body;
+pub mod cli;
+pub mod compress;
+pub mod conditional;
+pub mod config;
+pub mod connection;
 pub mod defaults;
+pub mod files;
+pub mod handler;
+pub mod headers;
+pub mod hpack;
+pub mod http2;
+pub mod listener;
+pub mod middleware;
+pub mod mime;
+pub mod parser;
+pub mod pool;
+pub mod range;
+pub mod resolve;
+pub mod router;
 pub mod status;
+pub mod stream;
+pub mod tls;
+pub mod vhost;
 
+use serde::{Deserialize, Serialize};
+use url::form_urlencoded;
 use urlencoding::decode;
 
 use std::{
     collections::HashMap,
-    env, fmt, fs,
-    io::{BufRead, BufReader},
-    net::TcpStream,
-    path::{MAIN_SEPARATOR_STR, PathBuf},
+    fmt,
+    io::{self, BufRead, Write},
+    path::{Component, PathBuf},
+    str::FromStr,
+    time::SystemTime,
 };
 
 use crate::{
-    defaults::{INDEX_EXTENSIONS, LOGGING, ROOT_FOLDER},
-    status::{ClientErrorCode, HTTPStatusCode, ServerErrorCode},
+    body::{Body, RequestBody},
+    defaults::SERVER_NAME,
+    files::Files,
+    headers::HeaderMap,
+    parser::ParseError,
+    status::{ClientErrorCode, HTTPStatusCode, RedirectionCode, SuccessCode},
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum HTTPMethod {
     // https://developer.mozilla.org/en-US/docs/Web/HTTP/Reference/Methods
     GET,
@@ -30,8 +60,10 @@ pub enum HTTPMethod {
     PATCH,
 }
 
-impl HTTPMethod {
-    fn from_str(input: &str) -> Result<HTTPMethod, &str> {
+impl FromStr for HTTPMethod {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<HTTPMethod, String> {
         match input {
             "GET" => Ok(HTTPMethod::GET),
             "HEAD" => Ok(HTTPMethod::HEAD),
@@ -42,7 +74,7 @@ impl HTTPMethod {
             "OPTIONS" => Ok(HTTPMethod::OPTIONS),
             "TRACE" => Ok(HTTPMethod::TRACE),
             "PATCH" => Ok(HTTPMethod::PATCH),
-            s => Err(s),
+            s => Err(String::from(s)),
         }
     }
 }
@@ -63,49 +95,135 @@ impl fmt::Display for HTTPMethod {
     }
 }
 
+#[derive(Debug, Clone)]
 pub struct RequestURL {
     path: PathBuf,
+    segments: Vec<String>,
+    query: Option<String>,
     parameters: Option<Vec<(String, String)>>,
+    // -> "/docs/" rather than "/docs", the root always counts as a directory
+    trailing_slash: bool,
 }
 
 impl RequestURL {
-    pub fn normalize(input: &str) -> RequestURL {
-        // -> Uri Decoding
-        let decoded = match decode(input) {
-            Ok(d) => d.into_owned(),
-            Err(_) => todo!(),
+    pub fn normalize(input: &str) -> Result<RequestURL, HTTPStatusCode> {
+        let bad_request = HTTPStatusCode::ClientError(ClientErrorCode::BadRequest);
+
+        // -> Drop the fragment, then split path and query before decoding either
+        let input = match input.split_once('#') {
+            Some((before, _)) => before,
+            None => input,
         };
 
-        // -> Sanitize relative dots
-        let mut valid_parts: Vec<String> = Vec::new();
-        let split = decoded.split("/");
+        let (raw_path, query) = match input.split_once('?') {
+            Some((p, q)) => (p, Some(String::from(q))),
+            None => (input, None),
+        };
+
+        // -> Split on "/" first so encoded separators can never create new segments
+        let mut segments: Vec<String> = Vec::new();
+
+        for chunk in raw_path.split('/') {
+            // -> Uri Decoding
+            let decoded = match decode(chunk) {
+                Ok(d) => d.into_owned(),
+                Err(_) => return Err(bad_request),
+            };
 
-        for chunk in split {
-            match chunk {
+            if decoded.contains(['/', '\\', '\0']) {
+                return Err(bad_request);
+            }
+
+            // -> Sanitize relative dots
+            match decoded.as_str() {
                 "" => (),
                 "." => (),
                 ".." => {
-                    valid_parts.pop();
+                    segments.pop();
                 }
-                _ => valid_parts.push(String::from(chunk)),
+                _ => segments.push(decoded),
             }
         }
 
-        let valid_string = valid_parts.join(MAIN_SEPARATOR_STR);
+        let trailing_slash =
+            segments.is_empty() || matches!(raw_path.rsplit('/').next(), Some("" | "." | ".."));
+
+        let path: PathBuf = segments.iter().collect();
+
+        // -> Anything but plain names (drive prefixes, roots) would escape the document root
+        if !path.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(bad_request);
+        }
+
+        // -> application/x-www-form-urlencoded, "+" is a space and keys may repeat
+        let parameters: Option<Vec<(String, String)>> = query.as_ref().map(|q| {
+            form_urlencoded::parse(q.as_bytes())
+                .map(|(key, value)| (key.into_owned(), value.into_owned()))
+                .collect()
+        });
+
+        Ok(RequestURL {
+            path,
+            segments,
+            query,
+            parameters,
+            trailing_slash,
+        })
+    }
+
+    pub fn has_trailing_slash(&self) -> bool {
+        self.trailing_slash
+    }
+
+    // -> Normalized path, percent-encoded again, e.g. for a Location header
+    pub fn encoded_path(&self) -> String {
+        let encoded: Vec<String> = self
+            .segments
+            .iter()
+            .map(|s| urlencoding::encode(s).into_owned())
+            .collect();
+
+        match self.trailing_slash && !encoded.is_empty() {
+            true => format!("/{}/", encoded.join("/")),
+            false => format!("/{}", encoded.join("/")),
+        }
+    }
+
+    pub fn segments(&self) -> &[String] {
+        &self.segments
+    }
 
-        let path = PathBuf::from(valid_string);
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub fn query(&self) -> Option<&str> {
+        self.query.as_deref()
+    }
 
-        // -> only absolute paths
-        let _ = path.strip_prefix(".");
+    pub fn parameters(&self) -> Option<&Vec<(String, String)>> {
+        self.parameters.as_ref()
+    }
 
-        // -> trim root slash
-        let _ = path.strip_prefix("/");
-        let _ = path.strip_prefix(MAIN_SEPARATOR_STR);
+    pub fn param(&self, key: &str) -> Option<&str> {
+        self.parameters
+            .iter()
+            .flatten()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
 
-        // -> Cut off at first ? (parameters)
-        let parameters: Option<Vec<(String, String)>> = None;
+    pub fn params(&self, key: &str) -> Vec<&str> {
+        self.parameters
+            .iter()
+            .flatten()
+            .filter(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+            .collect()
+    }
 
-        RequestURL { path, parameters }
+    pub fn has_param(&self, key: &str) -> bool {
+        self.param(key).is_some()
     }
 }
 
@@ -114,187 +232,205 @@ pub struct HTTPRequest {
     pub method: HTTPMethod,
     pub path: PathBuf,
     pub version: String,
-    pub headers: HashMap<String, String>,
-    // body: Option<String>,
+    pub headers: HeaderMap,
+    pub params: HashMap<String, String>,
+    pub body: RequestBody,
 }
 
 impl HTTPRequest {
-    pub fn get_file(input_url: RequestURL) -> Result<String, HTTPStatusCode> {
-        let mut root_path = HTTPRequest::get_root_dir();
-        root_path.push(input_url.path);
-
-        if !root_path.exists() {
-            log(format!(
-                "File or Directory \"{}\" doesn't exist",
-                root_path.display()
-            ));
-            return Err(HTTPStatusCode::ClientError(ClientErrorCode::NotFound));
-        }
+    pub fn get_file(input_url: RequestURL) -> Result<(Body, String), HTTPStatusCode> {
+        Files::from_config(config::get()).get_file(&input_url)
+    }
 
-        if root_path.is_dir() {
-            match HTTPRequest::get_index_path(root_path) {
-                Ok(p) => root_path = p,
-                Err(c) => return Err(c),
-            }
-        }
+    pub fn from_buf_reader<R: BufRead>(buf_reader: &mut R) -> Result<HTTPRequest, ParseError> {
+        parser::parse_request(buf_reader)
+    }
 
-        log(format!("Getting file: {}", root_path.display()));
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.get(name).map(|p| p.as_str())
+    }
 
-        match fs::read_to_string(root_path) {
-            Ok(str) => Ok(str),
-            Err(_) => Err(HTTPStatusCode::ServerError(
-                ServerErrorCode::InternalServerError,
-            )),
-        }
+    pub fn url(&self) -> Result<RequestURL, HTTPStatusCode> {
+        RequestURL::normalize(&self.path.to_string_lossy())
     }
 
-    fn get_index_path(path: PathBuf) -> Result<PathBuf, HTTPStatusCode> {
-        let mut res: Option<PathBuf> = None;
+    pub fn has_connection_token(&self, token: &str) -> bool {
+        self.headers.has_token("Connection", token)
+    }
 
-        for ext in INDEX_EXTENSIONS {
-            let test_file_name = format!("{}{}", "index", ext);
-            let mut test_path = path.clone();
-            test_path.push(test_file_name);
+    // -> Host header without the port, lowercased
+    pub fn host(&self) -> Option<String> {
+        self.headers.get("Host").map(vhost::normalize_host)
+    }
 
-            if test_path.exists() {
-                res = Some(test_path);
-                break;
-            }
+    pub fn keep_alive(&self) -> bool {
+        match self.version.as_str() {
+            "1.1" => !self.has_connection_token("close"),
+            _ => self.has_connection_token("keep-alive"),
         }
+    }
+}
 
-        match res {
-            Some(r) => Ok(r),
-            None => Err(HTTPStatusCode::ClientError(ClientErrorCode::NotFound)),
-        }
+#[derive(Debug)]
+pub struct HTTPResponse {
+    pub status: HTTPStatusCode,
+    pub version: String,
+    pub headers: HeaderMap,
+    pub contents: Option<Body>,
+}
+
+impl HTTPResponse {
+    pub fn builder() -> ResponseBuilder {
+        ResponseBuilder::new()
     }
 
-    fn get_root_dir() -> PathBuf {
-        PathBuf::from(match env::var("ROOT") {
-            Ok(value) => value,
-            Err(_) => String::from(ROOT_FOLDER),
-        })
+    pub fn redirect(code: RedirectionCode, location: impl Into<String>) -> HTTPResponse {
+        ResponseBuilder::new()
+            .status(HTTPStatusCode::Redirection(code))
+            .header("Location", location)
+            .build()
     }
 
-    pub fn from_buf_reader(
-        buf_reader: BufReader<&TcpStream>,
-    ) -> Result<HTTPRequest, HTTPStatusCode> {
-        let lines = buf_reader.lines();
+    // -> For HEAD, the headers stay exactly as they would be for GET
+    pub fn strip_body(&mut self) {
+        self.complete_headers();
+        self.contents = None;
+    }
 
-        let mut request: Option<HTTPRequest> = None;
+    pub fn write_to<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        let version = &self.version;
+        let status_code = self.status.to_value();
+        let status_message = &self.status;
+        let status_line = format!("HTTP/{version} {status_code} {status_message}");
 
-        for iteration in lines.enumerate() {
-            let (i, line) = iteration;
-            let line_str = line.unwrap();
-            if line_str.is_empty() {
-                break;
-            }
+        self.complete_headers();
 
-            if i == 0 {
-                let split: Vec<&str> = line_str.split_whitespace().collect();
+        // -> Refuse to send anything rather than a head that could be split
+        self.headers.validate()?;
 
-                if split.len() != 3 {
-                    return Err(HTTPStatusCode::ServerError(
-                        ServerErrorCode::InternalServerError,
-                    ));
-                }
+        let head = format!("{status_line}\r\n{}\r\n", self.headers);
+        writer.write_all(head.as_bytes())?;
 
-                let method = match HTTPMethod::from_str(split[0]) {
-                    Ok(m) => m,
-                    Err(_) => todo!(),
-                };
-
-                let path = PathBuf::from(split[1]);
-
-                let version = match get_http_version_from_string(split[2]) {
-                    Ok(v) => v,
-                    Err(_) => todo!(),
-                };
-
-                request = Some(HTTPRequest {
-                    method,
-                    path,
-                    version,
-                    headers: HashMap::new(),
-                })
-            } else {
-                let split = line_str.split_once(":").unwrap();
-
-                let key = split.0;
-                let value = split.1.trim();
-
-                match request {
-                    Some(ref mut r) => r.headers.insert(String::from(key), String::from(value)),
-                    None => {
-                        return Err(HTTPStatusCode::ServerError(
-                            ServerErrorCode::InternalServerError,
-                        ));
-                    }
-                };
-            }
+        if let Some(contents) = &mut self.contents {
+            contents.write_to(writer)?;
+        }
+
+        writer.flush()
+    }
+
+    pub(crate) fn complete_headers(&mut self) {
+        if !self.headers.contains("Date") {
+            self.headers
+                .insert("Date", httpdate::fmt_http_date(SystemTime::now()));
         }
 
-        match request {
-            Some(r) => Ok(r),
-            None => Err(HTTPStatusCode::ServerError(
-                ServerErrorCode::InternalServerError,
-            )),
+        if !self.headers.contains("Server") {
+            self.headers.insert("Server", SERVER_NAME);
+        }
+
+        // -> 1xx, 204 and 304 responses never carry a body or its length
+        let bodiless = matches!(self.status.to_value(), 100..=199 | 204 | 304);
+
+        if !bodiless && !self.headers.contains("Content-Length") {
+            let length = match &self.contents {
+                Some(c) => c.len(),
+                None => 0,
+            };
+
+            self.headers.insert("Content-Length", length.to_string());
         }
     }
 }
 
-#[derive(Debug)]
-pub struct HTTPResponse {
-    pub status: HTTPStatusCode,
-    pub version: String,
-    pub contents: Option<String>,
+pub struct ResponseBuilder {
+    response: HTTPResponse,
 }
 
-impl HTTPResponse {
-    pub fn to_string(&self) -> String {
-        let version = &self.version;
-        let status_code = self.status.to_value();
-        let status_message = &self.status;
-        let status_line = format!("HTTP/{version} {status_code} {status_message}");
+impl ResponseBuilder {
+    pub fn new() -> ResponseBuilder {
+        ResponseBuilder {
+            response: HTTPResponse {
+                status: HTTPStatusCode::Success(SuccessCode::OK),
+                version: String::from("1.1"),
+                headers: HeaderMap::new(),
+                contents: None,
+            },
+        }
+    }
 
-        let contents = match &self.contents {
-            Some(c) => String::from(c),
-            None => String::from(""),
-        };
-        let length = contents.len();
+    pub fn status(mut self, status: HTTPStatusCode) -> ResponseBuilder {
+        self.response.status = status;
+        self
+    }
+
+    pub fn version(mut self, version: &str) -> ResponseBuilder {
+        self.response.version = String::from(version);
+        self
+    }
 
-        let res_string = format!("{status_line}\r\nContent-Length: {length}\r\n\r\n{contents}");
+    pub fn header(mut self, name: &str, value: impl Into<String>) -> ResponseBuilder {
+        self.response.headers.append(name, value);
+        self
+    }
 
-        res_string
+    pub fn body(mut self, body: impl Into<Body>) -> ResponseBuilder {
+        self.response.contents = Some(body.into());
+        self
+    }
 
-        // TODO: Content-Type Header (from file type?)
+    pub fn build(self) -> HTTPResponse {
+        self.response
     }
 }
 
-fn get_http_version_from_string(input: &str) -> Result<String, ()> {
-    let prefix = "HTTP/";
-
-    if !input.starts_with(prefix) {
-        return Err(());
+impl Default for ResponseBuilder {
+    fn default() -> Self {
+        ResponseBuilder::new()
     }
+}
 
-    Ok(input.replace(prefix, ""))
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
 }
 
-fn log(m: String) {
-    let active: bool = match env::var("logging") {
-        Ok(value) => bool_from_string(value),
-        Err(_) => LOGGING,
-    };
+impl FromStr for LogLevel {
+    type Err = String;
 
-    if active {
-        // Todo: Log file
-        println!("{}", m);
+    fn from_str(input: &str) -> Result<LogLevel, String> {
+        match input.to_ascii_lowercase().as_str() {
+            "error" => Ok(LogLevel::Error),
+            "warn" | "warning" => Ok(LogLevel::Warn),
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            s => Err(String::from(s)),
+        }
     }
 }
 
-fn bool_from_string(input: String) -> bool {
-    match input.parse::<bool>() {
-        Ok(v) => v,
-        Err(_) => false,
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogLevel::Error => write!(f, "error"),
+            LogLevel::Warn => write!(f, "warn"),
+            LogLevel::Info => write!(f, "info"),
+            LogLevel::Debug => write!(f, "debug"),
+        }
+    }
+}
+
+fn log(level: LogLevel, m: String) {
+    let config = config::get();
+
+    if config.logging && level <= config.log_level {
+        // Todo: Log file
+        println!("{}", m);
     }
 }
@@ -0,0 +1,137 @@
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, ToSocketAddrs},
+};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::{config::ServerConfig, defaults::TLS_PORT};
+
+// -> Listen entries when any are configured, otherwise every host on the port
+pub fn listen_addresses(config: &ServerConfig) -> Result<Vec<SocketAddr>, String> {
+    let port = config.port;
+    let mut addresses: Vec<SocketAddr> = Vec::new();
+
+    for entry in &config.listen {
+        push_unique(&mut addresses, parse_listen(entry, port)?);
+    }
+
+    if config.listen.is_empty() {
+        for host in config.hosts.iter().filter(|h| !h.is_empty()) {
+            push_unique(&mut addresses, resolve_host(host, port)?);
+        }
+    }
+
+    if addresses.is_empty() {
+        return Err(String::from("No address to listen on"));
+    }
+
+    Ok(addresses)
+}
+
+// -> Addresses of the HTTPS listeners, a bare host gets port 443
+pub fn tls_addresses(config: &ServerConfig) -> Result<Vec<SocketAddr>, String> {
+    let mut addresses: Vec<SocketAddr> = Vec::new();
+
+    for entry in &config.tls.listen {
+        push_unique(&mut addresses, parse_listen(entry, TLS_PORT)?);
+    }
+
+    Ok(addresses)
+}
+
+pub fn bind_all(addresses: &[SocketAddr]) -> Result<Vec<TcpListener>, String> {
+    let mut listeners = Vec::with_capacity(addresses.len());
+
+    for addr in addresses {
+        // -> "::" also takes IPv4 unless an IPv4 address shares the port
+        let dual_stack = addr.ip().is_unspecified()
+            && addr.is_ipv6()
+            && !addresses
+                .iter()
+                .any(|other| other.is_ipv4() && other.port() == addr.port());
+
+        match bind(*addr, dual_stack) {
+            Ok(listener) => listeners.push(listener),
+            Err(e) => return Err(format!("Unable to listen on {addr}: {e}")),
+        }
+    }
+
+    Ok(listeners)
+}
+
+pub fn bind(addr: SocketAddr, dual_stack: bool) -> io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+
+    if addr.is_ipv6() {
+        socket.set_only_v6(!dual_stack)?;
+    }
+
+    #[cfg(not(windows))]
+    socket.set_reuse_address(true)?;
+
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+
+    Ok(socket.into())
+}
+
+// -> Address a local client can connect to, used to wake a blocked accept call
+pub fn wake_address(addr: SocketAddr) -> SocketAddr {
+    match addr.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => {
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), addr.port())
+        }
+        IpAddr::V6(ip) if ip.is_unspecified() => {
+            SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), addr.port())
+        }
+        _ => addr,
+    }
+}
+
+fn parse_listen(entry: &str, default_port: u16) -> Result<SocketAddr, String> {
+    let entry = entry.trim();
+
+    // -> "8080" and ":8080" mean every interface on that port
+    let port_only = entry.strip_prefix(':').unwrap_or(entry);
+    if let Ok(port) = port_only.parse::<u16>() {
+        return Ok(SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), port));
+    }
+
+    if let Ok(addr) = entry.parse::<SocketAddr>() {
+        return Ok(addr);
+    }
+
+    match entry.rsplit_once(':') {
+        Some((host, port)) if !host.ends_with(':') && !host.is_empty() => {
+            let port = match port.parse::<u16>() {
+                Ok(p) => p,
+                Err(_) => return Err(format!("\"{entry}\" has an invalid port")),
+            };
+            resolve_host(host, port)
+        }
+        _ => resolve_host(entry, default_port),
+    }
+}
+
+fn resolve_host(host: &str, port: u16) -> Result<SocketAddr, String> {
+    let trimmed = host.trim_start_matches('[').trim_end_matches(']');
+
+    if let Ok(ip) = trimmed.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, port));
+    }
+
+    match (trimmed, port).to_socket_addrs() {
+        Ok(mut addrs) => match addrs.next() {
+            Some(addr) => Ok(addr),
+            None => Err(format!("Host \"{host}\" has no address")),
+        },
+        Err(e) => Err(format!("Unable to resolve host \"{host}\": {e}")),
+    }
+}
+
+fn push_unique(addresses: &mut Vec<SocketAddr>, addr: SocketAddr) {
+    if !addresses.contains(&addr) {
+        addresses.push(addr);
+    }
+}
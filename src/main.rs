@@ -1,80 +1,241 @@
 use dotenv::dotenv;
 
 use std::{
-    io::{BufReader, prelude::*},
-    net::{TcpListener, TcpStream},
+    env,
+    net::{SocketAddr, TcpListener, TcpStream},
+    process::ExitCode,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::Duration,
 };
 
 use rust_web_server::{
-    HTTPRequest, HTTPResponse, RequestURL,
-    status::{HTTPStatusCode, ServerErrorCode, SuccessCode},
+    cli::{Args, Command, USAGE},
+    compress::Compression,
+    config::{self, ServerConfig},
+    connection::handle_connection,
+    handler::Stack,
+    listener::{bind_all, listen_addresses, tls_addresses, wake_address},
+    middleware::Logger,
+    pool::WorkerPool,
+    stream::Stream,
+    tls::{HttpsRedirect, TlsAcceptor},
+    vhost::VirtualHosts,
 };
 
-fn main() {
+fn main() -> ExitCode {
     // Setup
     dotenv().ok();
 
-    let listener = match TcpListener::bind("127.0.0.1:7878") {
-        Ok(listener) => listener,
+    let args = match Args::parse(env::args().skip(1)) {
+        Ok(args) => args,
         Err(e) => {
-            print!("{}", e);
-            return;
+            eprintln!("{}\nRun with --help for usage", e);
+            return ExitCode::from(2);
         }
     };
 
-    for stream in listener.incoming() {
-        let stream = match stream {
-            Ok(s) => s,
-            Err(_) => {
-                eprintln!("Unable to parse Stream");
-                continue;
+    match args.command {
+        Command::Help => {
+            print!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Command::Version => {
+            println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+            return ExitCode::SUCCESS;
+        }
+        Command::Serve | Command::CheckConfig => (),
+    }
+
+    let config = match ServerConfig::load(&args) {
+        Ok(config) => config::install(config),
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if args.print_config {
+        return match config.to_toml() {
+            Ok(toml) => {
+                print!("{}", toml);
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                ExitCode::FAILURE
             }
         };
+    }
 
-        handle_connection(stream);
+    let addresses = match listen_addresses(config) {
+        Ok(addresses) => addresses,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let secure_addresses = match tls_addresses(config) {
+        Ok(addresses) => addresses,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    // -> Certificates are loaded up front, so a bad one fails the start and check-config
+    let acceptor = match secure_addresses.is_empty() {
+        true => None,
+        false => match TlsAcceptor::from_config(config) {
+            Ok(acceptor) => Some(acceptor),
+            Err(e) => {
+                eprintln!("{}", e);
+                return ExitCode::FAILURE;
+            }
+        },
+    };
+
+    if args.command == Command::CheckConfig {
+        let urls: Vec<String> = addresses
+            .iter()
+            .map(|a| format!("http://{a}"))
+            .chain(secure_addresses.iter().map(|a| format!("https://{a}")))
+            .collect();
+        println!("Configuration OK, would listen on {}", urls.join(", "));
+        return ExitCode::SUCCESS;
     }
-}
 
-fn handle_connection(mut stream: TcpStream) {
-    let buf_reader = BufReader::new(&stream);
+    // -> Plaintext listeners first, then the HTTPS ones
+    let all_addresses: Vec<SocketAddr> = addresses
+        .iter()
+        .chain(secure_addresses.iter())
+        .copied()
+        .collect();
 
-    let request_res = HTTPRequest::from_buf_reader(buf_reader);
+    let listeners = match bind_all(&all_addresses) {
+        Ok(listeners) => listeners,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
 
-    if request_res.is_err() {
-        let response = HTTPResponse {
-            status: request_res.unwrap_err(),
-            version: String::from("1.1"),
-            contents: None,
-        };
+    let local_addrs: Vec<SocketAddr> = listeners
+        .iter()
+        .filter_map(|l| l.local_addr().ok())
+        .collect();
 
-        stream.write_all(response.to_string().as_bytes()).unwrap();
+    for (i, addr) in local_addrs.iter().enumerate() {
+        match i < addresses.len() {
+            true => println!("Listening on http://{}", addr),
+            false => println!("Listening on https://{}", addr),
+        }
+    }
 
-        return;
+    let mut app = Stack::new(VirtualHosts::from_config(config)).wrap(Logger);
+    if config.compression {
+        app = app.wrap(Compression::from_config(config));
     }
+    let app = Arc::new(app);
 
-    let request = request_res.unwrap();
+    let redirect = match (config.tls.redirect, secure_addresses.first()) {
+        (true, Some(addr)) => Some(Stack::new(HttpsRedirect::new(addr.port())).wrap(Logger)),
+        _ => None,
+    };
+
+    let mut pool = WorkerPool::from_config(config, move |stream: Stream| {
+        match (&redirect, stream.is_secure()) {
+            (Some(redirect), false) => handle_connection(stream, redirect),
+            _ => handle_connection(stream, app.as_ref()),
+        }
+    });
+
+    // -> Stop accepting on Ctrl-C and wake every blocked accept call
+    let running = Arc::new(AtomicBool::new(true));
+    {
+        let running = Arc::clone(&running);
+        let result = ctrlc::set_handler(move || {
+            running.store(false, Ordering::SeqCst);
+
+            for addr in &local_addrs {
+                let _ = TcpStream::connect(wake_address(*addr));
+            }
+        });
+
+        if let Err(e) = result {
+            eprintln!("Unable to register shutdown handler: {}", e);
+        }
+    }
 
-    let response = match request.version.as_str() {
-        "1.1" => match HTTPRequest::get_file(RequestURL::normalize(request.path.to_str().unwrap()))
+    thread::scope(|scope| {
+        for (i, listener) in listeners.iter().enumerate() {
+            let tls = match i < addresses.len() {
+                true => None,
+                false => acceptor.as_ref(),
+            };
+            let (pool, running) = (&pool, &running);
+            scope.spawn(move || accept(listener, tls, pool, running));
+        }
+
+        // -> Changed certificate files are picked up by new handshakes, open connections keep theirs
+        if let Some(acceptor) = &acceptor
+            && config.tls.reload_interval > 0
         {
-            Ok(file_str) => HTTPResponse {
-                status: HTTPStatusCode::Success(SuccessCode::OK),
-                version: String::from("1.1"),
-                contents: Some(file_str),
-            },
-            Err(code) => HTTPResponse {
-                status: code,
-                version: String::from("1.1"),
-                contents: None,
-            },
-        },
+            let running = &running;
+            scope.spawn(move || {
+                let mut elapsed = 0;
+                while running.load(Ordering::SeqCst) {
+                    thread::sleep(Duration::from_secs(1));
+                    elapsed += 1;
+                    if elapsed >= config.tls.reload_interval {
+                        acceptor.certificates().reload();
+                        elapsed = 0;
+                    }
+                }
+            });
+        }
+    });
 
-        &_ => HTTPResponse {
-            status: HTTPStatusCode::ServerError(ServerErrorCode::HTTPVersionNotSupported),
-            version: String::from("1.1"),
-            contents: None,
-        },
-    };
+    println!("Shutting down, waiting for open connections");
+    pool.shutdown();
 
-    stream.write_all(response.to_string().as_bytes()).unwrap()
+    ExitCode::SUCCESS
+}
+
+fn accept(
+    listener: &TcpListener,
+    tls: Option<&TlsAcceptor>,
+    pool: &WorkerPool,
+    running: &AtomicBool,
+) {
+    for stream in listener.incoming() {
+        if !running.load(Ordering::SeqCst) {
+            break;
+        }
+
+        let stream = match stream {
+            Ok(s) => s,
+            Err(_) => {
+                eprintln!("Unable to parse Stream");
+                continue;
+            }
+        };
+
+        let stream = match tls {
+            Some(acceptor) => match acceptor.accept(stream) {
+                Ok(s) => s,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    continue;
+                }
+            },
+            None => Stream::Plain(stream),
+        };
+
+        pool.execute(stream);
+    }
 }
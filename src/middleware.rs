@@ -0,0 +1,257 @@
+use std::time::Instant;
+
+use crate::{
+    HTTPRequest, HTTPResponse, LogLevel,
+    handler::{Handler, Middleware},
+    headers::HeaderMap,
+    log,
+    status::{ClientErrorCode, HTTPStatusCode},
+};
+
+pub struct Logger;
+
+impl Middleware for Logger {
+    fn handle(&self, request: &mut HTTPRequest, next: &dyn Handler) -> HTTPResponse {
+        let start = Instant::now();
+        let method = request.method;
+        let path = request.path.display().to_string();
+
+        let response = next.handle(request);
+
+        log(
+            LogLevel::Info,
+            format!(
+                "{} {} -> {} ({} ms)",
+                method,
+                path,
+                response.status.to_value(),
+                start.elapsed().as_millis()
+            ),
+        );
+
+        response
+    }
+}
+
+// -> Adds headers to every response that doesn't set them itself
+pub struct DefaultHeaders {
+    headers: HeaderMap,
+}
+
+impl DefaultHeaders {
+    pub fn new() -> DefaultHeaders {
+        DefaultHeaders {
+            headers: HeaderMap::new(),
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: impl Into<String>) -> DefaultHeaders {
+        self.headers.append(name, value);
+        self
+    }
+}
+
+impl Default for DefaultHeaders {
+    fn default() -> Self {
+        DefaultHeaders::new()
+    }
+}
+
+impl Middleware for DefaultHeaders {
+    fn handle(&self, request: &mut HTTPRequest, next: &dyn Handler) -> HTTPResponse {
+        let mut response = next.handle(request);
+
+        for (name, value) in self.headers.iter() {
+            if !response.headers.contains(name) {
+                response.headers.append(name, value);
+            }
+        }
+
+        response
+    }
+}
+
+pub struct BasicAuth {
+    realm: String,
+    // -> Expected base64 "name:password" tokens, one per user
+    credentials: Vec<String>,
+}
+
+impl BasicAuth {
+    pub fn new(realm: &str) -> BasicAuth {
+        BasicAuth {
+            realm: String::from(realm),
+            credentials: Vec::new(),
+        }
+    }
+
+    pub fn user(mut self, name: &str, password: &str) -> BasicAuth {
+        let token = base64_encode(format!("{name}:{password}").as_bytes());
+        self.credentials.push(token);
+        self
+    }
+}
+
+impl Middleware for BasicAuth {
+    fn handle(&self, request: &mut HTTPRequest, next: &dyn Handler) -> HTTPResponse {
+        let credentials = request
+            .headers
+            .get("Authorization")
+            .and_then(|v| v.trim().split_once(' '));
+
+        // -> Every user is compared, so the time taken doesn't tell which one came close
+        let authorized = match credentials {
+            Some((scheme, token)) if scheme.eq_ignore_ascii_case("Basic") => {
+                let token = token.trim().as_bytes();
+                self.credentials.iter().fold(false, |found, c| {
+                    found | constant_time_eq(c.as_bytes(), token)
+                })
+            }
+            _ => false,
+        };
+
+        if !authorized {
+            return HTTPResponse::builder()
+                .status(HTTPStatusCode::ClientError(ClientErrorCode::Unauthorized))
+                .header(
+                    "WWW-Authenticate",
+                    format!("Basic realm=\"{}\", charset=\"UTF-8\"", self.realm),
+                )
+                .build();
+        }
+
+        next.handle(request)
+    }
+}
+
+// -> Only the length can end the comparison early, never the position of a wrong byte
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn base64_encode(input: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut output = String::with_capacity(input.len().div_ceil(3) * 4);
+
+    for chunk in input.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                output.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    use crate::parser::parse_request;
+
+    fn secret(_: &mut HTTPRequest) -> HTTPResponse {
+        HTTPResponse::builder().body("secret").build()
+    }
+
+    fn send(auth: &BasicAuth, authorization: Option<&str>) -> HTTPResponse {
+        let mut input = String::from("GET / HTTP/1.1\r\nHost: a.test\r\n");
+        if let Some(value) = authorization {
+            input.push_str(&format!("Authorization: {value}\r\n"));
+        }
+        input.push_str("\r\n");
+
+        let mut request = parse_request(&mut Cursor::new(input.into_bytes())).unwrap();
+        auth.handle(&mut request, &secret)
+    }
+
+    fn auth() -> BasicAuth {
+        BasicAuth::new("Admin area")
+            .user("bob", "hunter2")
+            .user("alice", "secret")
+    }
+
+    #[test]
+    fn lets_known_users_through() {
+        let response = send(&auth(), Some("Basic YWxpY2U6c2VjcmV0"));
+        assert_eq!(response.status.to_value(), 200);
+        assert_eq!(response.headers.get("WWW-Authenticate"), None);
+    }
+
+    #[test]
+    fn takes_the_scheme_in_any_case() {
+        assert_eq!(
+            send(&auth(), Some("basic YWxpY2U6c2VjcmV0"))
+                .status
+                .to_value(),
+            200
+        );
+        assert_eq!(
+            send(&auth(), Some("BASIC  YWxpY2U6c2VjcmV0 "))
+                .status
+                .to_value(),
+            200
+        );
+    }
+
+    #[test]
+    fn challenges_without_credentials() {
+        let response = send(&auth(), None);
+
+        assert_eq!(response.status.to_value(), 401);
+        assert_eq!(
+            response.headers.get("WWW-Authenticate"),
+            Some("Basic realm=\"Admin area\", charset=\"UTF-8\"")
+        );
+        assert!(response.contents.is_none());
+    }
+
+    #[test]
+    fn refuses_wrong_credentials() {
+        for value in [
+            "Basic YWxpY2U6d3Jvbmc=",
+            "Basic YWxpY2U6c2VjcmV",
+            "Basic",
+            "Basic ",
+            "Bearer YWxpY2U6c2VjcmV0",
+            "YWxpY2U6c2VjcmV0",
+        ] {
+            let response = send(&auth(), Some(value));
+            assert_eq!(response.status.to_value(), 401, "{value:?}");
+            assert!(response.headers.contains("WWW-Authenticate"));
+        }
+    }
+
+    #[test]
+    fn compares_in_constant_time() {
+        assert!(constant_time_eq(b"abc", b"abc"));
+        assert!(!constant_time_eq(b"abc", b"abd"));
+        assert!(!constant_time_eq(b"abc", b"abcd"));
+        assert!(constant_time_eq(b"", b""));
+    }
+
+    #[test]
+    fn encodes_base64() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foo"), "Zm9v");
+        assert_eq!(base64_encode(b"alice:secret"), "YWxpY2U6c2VjcmV0");
+    }
+}
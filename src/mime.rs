@@ -0,0 +1,140 @@
+use std::path::Path;
+
+use crate::{config, defaults::DEFAULT_MIME_TYPE};
+
+// https://developer.mozilla.org/en-US/docs/Web/HTTP/Guides/MIME_types/Common_types
+const MIME_TYPES: [(&str, &str); 70] = [
+    // -> Web
+    ("html", "text/html"),
+    ("htm", "text/html"),
+    ("css", "text/css"),
+    ("js", "text/javascript"),
+    ("mjs", "text/javascript"),
+    ("json", "application/json"),
+    ("map", "application/json"),
+    ("jsonld", "application/ld+json"),
+    ("webmanifest", "application/manifest+json"),
+    ("xml", "application/xml"),
+    ("xhtml", "application/xhtml+xml"),
+    ("rss", "application/rss+xml"),
+    ("atom", "application/atom+xml"),
+    ("wasm", "application/wasm"),
+    ("txt", "text/plain"),
+    ("md", "text/markdown"),
+    ("csv", "text/csv"),
+    ("ics", "text/calendar"),
+    ("vtt", "text/vtt"),
+    // -> Images
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("avif", "image/avif"),
+    ("svg", "image/svg+xml"),
+    ("ico", "image/vnd.microsoft.icon"),
+    ("bmp", "image/bmp"),
+    ("tif", "image/tiff"),
+    ("tiff", "image/tiff"),
+    ("apng", "image/apng"),
+    // -> Fonts
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("ttf", "font/ttf"),
+    ("otf", "font/otf"),
+    ("eot", "application/vnd.ms-fontobject"),
+    // -> Audio
+    ("mp3", "audio/mpeg"),
+    ("wav", "audio/wav"),
+    ("weba", "audio/webm"),
+    ("oga", "audio/ogg"),
+    ("opus", "audio/ogg"),
+    ("aac", "audio/aac"),
+    ("flac", "audio/flac"),
+    ("m4a", "audio/mp4"),
+    ("mid", "audio/midi"),
+    ("midi", "audio/midi"),
+    // -> Video
+    ("mp4", "video/mp4"),
+    ("m4v", "video/mp4"),
+    ("webm", "video/webm"),
+    ("ogv", "video/ogg"),
+    ("mov", "video/quicktime"),
+    ("avi", "video/x-msvideo"),
+    ("mpeg", "video/mpeg"),
+    ("3gp", "video/3gpp"),
+    // -> Archives
+    ("zip", "application/zip"),
+    ("gz", "application/gzip"),
+    ("tar", "application/x-tar"),
+    ("bz2", "application/x-bzip2"),
+    ("7z", "application/x-7z-compressed"),
+    ("rar", "application/vnd.rar"),
+    ("xz", "application/x-xz"),
+    ("zst", "application/zstd"),
+    // -> Documents
+    ("pdf", "application/pdf"),
+    ("rtf", "application/rtf"),
+    ("doc", "application/msword"),
+    (
+        "docx",
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    ),
+    ("xls", "application/vnd.ms-excel"),
+    (
+        "xlsx",
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    ),
+    ("epub", "application/epub+zip"),
+    ("bin", "application/octet-stream"),
+];
+
+// -> Non text/* types that are still sent as UTF-8 text
+pub const TEXT_TYPES: [&str; 10] = [
+    "application/json",
+    "application/ld+json",
+    "application/manifest+json",
+    "application/xml",
+    "application/xhtml+xml",
+    "application/rss+xml",
+    "application/atom+xml",
+    "application/javascript",
+    "image/svg+xml",
+    "text/javascript",
+];
+
+pub fn from_path(path: &Path) -> String {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => from_extension(ext),
+        None => String::from(DEFAULT_MIME_TYPE),
+    }
+}
+
+pub fn from_extension(ext: &str) -> String {
+    let ext = ext.to_ascii_lowercase();
+
+    // -> Entries from the config add to or replace the built-in table
+    let mime = match config::get().mime_types.get(&ext) {
+        Some(m) => m.as_str(),
+        None => MIME_TYPES
+            .iter()
+            .find(|(e, _)| *e == ext)
+            .map(|(_, m)| *m)
+            .unwrap_or(DEFAULT_MIME_TYPE),
+    };
+
+    with_charset(mime)
+}
+
+pub fn is_text(mime: &str) -> bool {
+    let essence = mime.split(';').next().unwrap_or("").trim();
+    essence.starts_with("text/") || TEXT_TYPES.contains(&essence)
+}
+
+fn with_charset(mime: &str) -> String {
+    if is_text(mime) && !mime.contains("charset=") {
+        format!("{mime}; charset=utf-8")
+    } else {
+        String::from(mime)
+    }
+}
@@ -0,0 +1,711 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{self, BufRead, Read},
+    path::PathBuf,
+};
+
+use url::Url;
+
+use crate::{
+    HTTPMethod, HTTPRequest,
+    body::{Framing, RequestBody},
+    defaults::{MAX_EMPTY_LINES, MAX_HEADER_COUNT, MAX_HEADER_SIZE, MAX_REQUEST_LINE},
+    headers::{HeaderMap, is_token_char},
+    status::{ClientErrorCode, HTTPStatusCode, ServerErrorCode},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    // -> Connection closed or went idle before a new request started
+    Closed,
+    Timeout,
+    MalformedRequestLine,
+    InvalidMethod,
+    UnknownMethod(String),
+    InvalidTarget,
+    URITooLong,
+    MalformedVersion,
+    UnsupportedVersion(String),
+    MalformedHeader,
+    TooManyHeaders,
+    HeadersTooLarge,
+    InvalidContentLength,
+    UnsupportedTransferEncoding,
+    ContentTooLarge,
+    ExpectationFailed,
+}
+
+impl ParseError {
+    pub fn status(&self) -> HTTPStatusCode {
+        match self {
+            ParseError::Closed | ParseError::Timeout => {
+                HTTPStatusCode::ClientError(ClientErrorCode::RequestTimeout)
+            }
+            ParseError::MalformedRequestLine
+            | ParseError::InvalidMethod
+            | ParseError::InvalidTarget
+            | ParseError::MalformedVersion
+            | ParseError::MalformedHeader
+            | ParseError::InvalidContentLength => {
+                HTTPStatusCode::ClientError(ClientErrorCode::BadRequest)
+            }
+            ParseError::UnknownMethod(_) | ParseError::UnsupportedTransferEncoding => {
+                HTTPStatusCode::ServerError(ServerErrorCode::NotImplemented)
+            }
+            ParseError::URITooLong => HTTPStatusCode::ClientError(ClientErrorCode::URITooLong),
+            ParseError::UnsupportedVersion(_) => {
+                HTTPStatusCode::ServerError(ServerErrorCode::HTTPVersionNotSupported)
+            }
+            ParseError::TooManyHeaders | ParseError::HeadersTooLarge => {
+                HTTPStatusCode::ClientError(ClientErrorCode::RequestHeaderFieldsTooLarge)
+            }
+            ParseError::ContentTooLarge => {
+                HTTPStatusCode::ClientError(ClientErrorCode::ContentTooLarge)
+            }
+            ParseError::ExpectationFailed => {
+                HTTPStatusCode::ClientError(ClientErrorCode::ExpectationFailed)
+            }
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Closed => write!(f, "connection closed"),
+            ParseError::Timeout => write!(f, "request timed out"),
+            ParseError::MalformedRequestLine => write!(f, "malformed request line"),
+            ParseError::InvalidMethod => write!(f, "invalid method token"),
+            ParseError::UnknownMethod(m) => write!(f, "unknown method \"{m}\""),
+            ParseError::InvalidTarget => write!(f, "invalid request target"),
+            ParseError::URITooLong => write!(f, "request target too long"),
+            ParseError::MalformedVersion => write!(f, "malformed HTTP version"),
+            ParseError::UnsupportedVersion(v) => write!(f, "unsupported HTTP version \"{v}\""),
+            ParseError::MalformedHeader => write!(f, "malformed header field"),
+            ParseError::TooManyHeaders => write!(f, "too many header fields"),
+            ParseError::HeadersTooLarge => write!(f, "header section too large"),
+            ParseError::InvalidContentLength => write!(f, "invalid Content-Length"),
+            ParseError::UnsupportedTransferEncoding => {
+                write!(f, "unsupported Transfer-Encoding")
+            }
+            ParseError::ContentTooLarge => write!(f, "request body too large"),
+            ParseError::ExpectationFailed => write!(f, "unsupported expectation"),
+        }
+    }
+}
+
+enum Line {
+    Complete(Vec<u8>),
+    TooLong(Vec<u8>),
+    Eof(Vec<u8>),
+}
+
+pub fn parse_request<R: BufRead>(reader: &mut R) -> Result<HTTPRequest, ParseError> {
+    // https://www.rfc-editor.org/rfc/rfc9112#name-message-format
+    let mut empty = 0;
+    let request_line = loop {
+        match read_line(reader, MAX_REQUEST_LINE) {
+            // -> RFC 9112 2.2 asks to skip at least one, a client sending nothing else is refused
+            Ok(Line::Complete(line)) if line.is_empty() => {
+                empty += 1;
+                if empty > MAX_EMPTY_LINES {
+                    return Err(ParseError::MalformedRequestLine);
+                }
+            }
+            Ok(Line::Complete(line)) => break line,
+            Ok(Line::TooLong(partial)) => return Err(too_long_request_line(&partial)),
+            Ok(Line::Eof(partial)) if partial.is_empty() => return Err(ParseError::Closed),
+            Ok(Line::Eof(_)) => return Err(ParseError::Timeout),
+            Err(_) => return Err(ParseError::Closed),
+        }
+    };
+
+    let (method, path, version) = parse_request_line(&request_line)?;
+    let headers = parse_headers(reader)?;
+
+    Ok(HTTPRequest {
+        method,
+        path,
+        version,
+        headers,
+        params: HashMap::new(),
+        body: RequestBody::empty(),
+    })
+}
+
+pub fn body_framing(headers: &HeaderMap, limit: u64) -> Result<Framing, ParseError> {
+    // https://www.rfc-editor.org/rfc/rfc9112#name-message-body-length
+    let encodings = headers.get_all("Transfer-Encoding");
+
+    if !encodings.is_empty() {
+        // -> Both framings at once is a smuggling vector, refuse instead of guessing
+        if headers.contains("Content-Length") {
+            return Err(ParseError::InvalidContentLength);
+        }
+
+        let codings: Vec<&str> = encodings
+            .iter()
+            .flat_map(|v| v.split(','))
+            .map(|c| c.trim())
+            .filter(|c| !c.is_empty())
+            .collect();
+
+        return match codings.as_slice() {
+            [coding] if coding.eq_ignore_ascii_case("chunked") => Ok(Framing::Chunked),
+            _ => Err(ParseError::UnsupportedTransferEncoding),
+        };
+    }
+
+    let lengths = headers.get_all("Content-Length");
+    let mut length: Option<u64> = None;
+
+    // -> Repeated values are only allowed when they all agree
+    for value in lengths.iter().flat_map(|v| v.split(',')) {
+        let value = value.trim();
+
+        if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(ParseError::InvalidContentLength);
+        }
+
+        let parsed = match value.parse::<u64>() {
+            Ok(v) => v,
+            Err(_) => return Err(ParseError::InvalidContentLength),
+        };
+
+        match length {
+            Some(l) if l != parsed => return Err(ParseError::InvalidContentLength),
+            _ => length = Some(parsed),
+        }
+    }
+
+    match length {
+        None | Some(0) => Ok(Framing::Empty),
+        Some(l) if l > limit => Err(ParseError::ContentTooLarge),
+        Some(l) => Ok(Framing::Length(l)),
+    }
+}
+
+fn parse_request_line(line: &[u8]) -> Result<(HTTPMethod, PathBuf, String), ParseError> {
+    let line = match std::str::from_utf8(line) {
+        Ok(l) => l,
+        Err(_) => return Err(ParseError::MalformedRequestLine),
+    };
+
+    // -> Exactly one SP between the three parts
+    let mut parts = line.split(' ');
+    let (method, target, version) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(m), Some(t), Some(v), None) => (m, t, v),
+        _ => return Err(ParseError::MalformedRequestLine),
+    };
+
+    if method.is_empty() || !method.bytes().all(is_token_char) {
+        return Err(ParseError::InvalidMethod);
+    }
+
+    let method = match method.parse::<HTTPMethod>() {
+        Ok(m) => m,
+        Err(m) => return Err(ParseError::UnknownMethod(m)),
+    };
+
+    let version = get_http_version_from_string(version)?;
+    let path = parse_target(&method, target)?;
+
+    Ok((method, path, version))
+}
+
+pub(crate) fn parse_target(method: &HTTPMethod, target: &str) -> Result<PathBuf, ParseError> {
+    if target.is_empty() || target.bytes().any(|b| b <= b' ' || b == 0x7f) {
+        return Err(ParseError::InvalidTarget);
+    }
+
+    // https://www.rfc-editor.org/rfc/rfc9112#name-request-target
+    if target.starts_with('/') {
+        return Ok(PathBuf::from(target));
+    }
+
+    if target == "*" {
+        return match method {
+            HTTPMethod::OPTIONS => Ok(PathBuf::from(target)),
+            _ => Err(ParseError::InvalidTarget),
+        };
+    }
+
+    if let HTTPMethod::CONNECT = method {
+        return Ok(PathBuf::from(target));
+    }
+
+    // -> absolute-form is reduced to its path and query
+    match Url::parse(target) {
+        Ok(url) if url.has_host() => {
+            let mut origin = String::from(url.path());
+
+            if let Some(query) = url.query() {
+                origin.push('?');
+                origin.push_str(query);
+            }
+
+            Ok(PathBuf::from(origin))
+        }
+        _ => Err(ParseError::InvalidTarget),
+    }
+}
+
+fn get_http_version_from_string(input: &str) -> Result<String, ParseError> {
+    let version = match input.strip_prefix("HTTP/") {
+        Some(v) => v.as_bytes(),
+        None => return Err(ParseError::MalformedVersion),
+    };
+
+    // -> HTTP-version = HTTP-name "/" DIGIT "." DIGIT
+    match version {
+        [major, b'.', minor] if major.is_ascii_digit() && minor.is_ascii_digit() => {
+            let version = format!("{}.{}", *major as char, *minor as char);
+
+            // -> RFC 9110 2.5, a later 1.x is handled as the highest minor version we implement
+            match (major, minor) {
+                (b'1', b'0' | b'1') => Ok(version),
+                (b'1', _) => Ok(String::from("1.1")),
+                _ => Err(ParseError::UnsupportedVersion(version)),
+            }
+        }
+        _ => Err(ParseError::MalformedVersion),
+    }
+}
+
+pub(crate) fn parse_headers<R: BufRead>(reader: &mut R) -> Result<HeaderMap, ParseError> {
+    let mut fields: Vec<(String, String)> = Vec::new();
+    let mut total = 0;
+
+    loop {
+        let line = match read_line(reader, MAX_HEADER_SIZE.saturating_sub(total)) {
+            Ok(Line::Complete(line)) => line,
+            Ok(Line::TooLong(_)) => return Err(ParseError::HeadersTooLarge),
+            Ok(Line::Eof(_)) | Err(_) => return Err(ParseError::Timeout),
+        };
+
+        if line.is_empty() {
+            break;
+        }
+
+        total += line.len() + 2;
+
+        // -> obs-fold continues the previous field value and is replaced with SP
+        if line[0] == b' ' || line[0] == b'\t' {
+            match fields.last_mut() {
+                Some((_, value)) => {
+                    let continuation = field_value(&line)?;
+
+                    if !continuation.is_empty() {
+                        value.push(' ');
+                        value.push_str(&continuation);
+                    }
+
+                    continue;
+                }
+                None => return Err(ParseError::MalformedHeader),
+            }
+        }
+
+        if fields.len() >= MAX_HEADER_COUNT {
+            return Err(ParseError::TooManyHeaders);
+        }
+
+        let colon = match line.iter().position(|b| *b == b':') {
+            Some(i) => i,
+            None => return Err(ParseError::MalformedHeader),
+        };
+
+        // -> No whitespace allowed between field name and colon
+        let name = &line[..colon];
+        if name.is_empty() || !name.iter().all(|b| is_token_char(*b)) {
+            return Err(ParseError::MalformedHeader);
+        }
+
+        let name = String::from_utf8_lossy(name).into_owned();
+        let value = field_value(&line[colon + 1..])?;
+
+        fields.push((name, value));
+    }
+
+    Ok(fields.into_iter().collect())
+}
+
+fn field_value(raw: &[u8]) -> Result<String, ParseError> {
+    let trimmed = raw.trim_ascii();
+
+    // -> field-vchar / SP / HTAB / obs-text, never bare CR, LF or NUL
+    if trimmed
+        .iter()
+        .any(|b| (*b < b' ' && *b != b'\t') || *b == 0x7f)
+    {
+        return Err(ParseError::MalformedHeader);
+    }
+
+    Ok(String::from_utf8_lossy(trimmed).into_owned())
+}
+
+fn read_line<R: BufRead>(reader: &mut R, limit: usize) -> io::Result<Line> {
+    let mut line = Vec::new();
+    let read = reader
+        .by_ref()
+        .take(limit as u64 + 2)
+        .read_until(b'\n', &mut line)?;
+
+    if read == 0 || line.last() != Some(&b'\n') {
+        if line.len() > limit {
+            return Ok(Line::TooLong(line));
+        }
+
+        return Ok(Line::Eof(line));
+    }
+
+    // -> CRLF, or a bare LF which recipients may accept
+    line.pop();
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+
+    if line.len() > limit {
+        return Ok(Line::TooLong(line));
+    }
+
+    Ok(Line::Complete(line))
+}
+
+fn too_long_request_line(partial: &[u8]) -> ParseError {
+    // -> A long line behind a well-formed method is an oversized target, not garbage
+    match partial.iter().position(|b| *b == b' ') {
+        Some(i) if i > 0 && partial[..i].iter().all(|b| is_token_char(*b)) => {
+            ParseError::URITooLong
+        }
+        _ => ParseError::MalformedRequestLine,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    fn parse(input: &str) -> Result<HTTPRequest, ParseError> {
+        parse_request(&mut Cursor::new(input.as_bytes().to_vec()))
+    }
+
+    fn parse_line(line: &str) -> Result<(HTTPMethod, PathBuf, String), ParseError> {
+        parse_request_line(line.as_bytes())
+    }
+
+    #[test]
+    fn parses_a_request() {
+        let request =
+            parse("GET /index.html?x=1 HTTP/1.1\r\nHost: a.test\r\nX-Empty:\r\n\r\n").unwrap();
+        assert_eq!(request.method, HTTPMethod::GET);
+        assert_eq!(request.path, PathBuf::from("/index.html?x=1"));
+        assert_eq!(request.version, "1.1");
+        assert_eq!(request.headers.get("host"), Some("a.test"));
+        assert_eq!(request.headers.get("X-Empty"), Some(""));
+    }
+
+    #[test]
+    fn refuses_bad_method_tokens() {
+        assert_eq!(parse_line("G(T / HTTP/1.1"), Err(ParseError::InvalidMethod));
+        assert_eq!(parse_line(" / HTTP/1.1"), Err(ParseError::InvalidMethod));
+        assert_eq!(
+            parse_line("BREW / HTTP/1.1"),
+            Err(ParseError::UnknownMethod(String::from("BREW")))
+        );
+    }
+
+    #[test]
+    fn refuses_targets_with_controls_or_spaces() {
+        assert_eq!(
+            parse_line("GET /a\x01b HTTP/1.1"),
+            Err(ParseError::InvalidTarget)
+        );
+        assert_eq!(
+            parse_line("GET /a\x7fb HTTP/1.1"),
+            Err(ParseError::InvalidTarget)
+        );
+        assert_eq!(
+            parse_line("GET /a\tb HTTP/1.1"),
+            Err(ParseError::InvalidTarget)
+        );
+        assert_eq!(
+            parse_line("GET /a b HTTP/1.1"),
+            Err(ParseError::MalformedRequestLine)
+        );
+        assert_eq!(
+            parse_line("GET  / HTTP/1.1"),
+            Err(ParseError::MalformedRequestLine)
+        );
+        assert_eq!(parse_line("GET * HTTP/1.1"), Err(ParseError::InvalidTarget));
+        assert_eq!(
+            parse_line("GET example.com HTTP/1.1"),
+            Err(ParseError::InvalidTarget)
+        );
+    }
+
+    #[test]
+    fn reduces_absolute_targets_to_their_path() {
+        let (_, path, _) = parse_line("GET http://a.test/x?y=1 HTTP/1.1").unwrap();
+        assert_eq!(path, PathBuf::from("/x?y=1"));
+    }
+
+    #[test]
+    fn parses_versions() {
+        assert_eq!(parse_line("GET / HTTP/1.0").unwrap().2, "1.0");
+        assert_eq!(parse_line("GET / HTTP/1.1").unwrap().2, "1.1");
+        // -> Later minor versions are answered as the highest one implemented
+        assert_eq!(parse_line("GET / HTTP/1.2").unwrap().2, "1.1");
+        assert_eq!(parse_line("GET / HTTP/1.9").unwrap().2, "1.1");
+    }
+
+    #[test]
+    fn refuses_other_versions() {
+        assert_eq!(
+            parse_line("GET / HTTP/2.0"),
+            Err(ParseError::UnsupportedVersion(String::from("2.0")))
+        );
+        assert_eq!(
+            parse_line("GET / HTTP/0.9"),
+            Err(ParseError::UnsupportedVersion(String::from("0.9")))
+        );
+        for version in ["HTTP/1", "HTTP/1.10", "http/1.1", "HTTP/x.1"] {
+            assert_eq!(
+                parse_line(&format!("GET / {version}")),
+                Err(ParseError::MalformedVersion),
+                "{version:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn maps_errors_to_status_codes() {
+        let status = |e: ParseError| e.status().to_value();
+
+        assert_eq!(status(ParseError::MalformedRequestLine), 400);
+        assert_eq!(status(ParseError::InvalidMethod), 400);
+        assert_eq!(status(ParseError::InvalidTarget), 400);
+        assert_eq!(status(ParseError::MalformedVersion), 400);
+        assert_eq!(status(ParseError::MalformedHeader), 400);
+        assert_eq!(status(ParseError::InvalidContentLength), 400);
+        assert_eq!(status(ParseError::Timeout), 408);
+        assert_eq!(status(ParseError::ContentTooLarge), 413);
+        assert_eq!(status(ParseError::URITooLong), 414);
+        assert_eq!(status(ParseError::ExpectationFailed), 417);
+        assert_eq!(status(ParseError::TooManyHeaders), 431);
+        assert_eq!(status(ParseError::HeadersTooLarge), 431);
+        assert_eq!(status(ParseError::UnknownMethod(String::from("BREW"))), 501);
+        assert_eq!(status(ParseError::UnsupportedTransferEncoding), 501);
+        assert_eq!(
+            status(ParseError::UnsupportedVersion(String::from("2.0"))),
+            505
+        );
+    }
+
+    #[test]
+    fn skips_a_few_empty_lines_before_the_request() {
+        assert!(parse("\r\n\r\nGET / HTTP/1.1\r\n\r\n").is_ok());
+
+        let flood = "\r\n".repeat(MAX_EMPTY_LINES + 1) + "GET / HTTP/1.1\r\n\r\n";
+        assert_eq!(parse(&flood).err(), Some(ParseError::MalformedRequestLine));
+    }
+
+    #[test]
+    fn replaces_obs_fold_with_a_space() {
+        let request = parse("GET / HTTP/1.1\r\nX-Long: one\r\n  two\r\n\ttwo\r\n\r\n").unwrap();
+        assert_eq!(request.headers.get("X-Long"), Some("one two two"));
+    }
+
+    #[test]
+    fn refuses_obs_fold_without_a_field() {
+        assert_eq!(
+            parse("GET / HTTP/1.1\r\n folded: value\r\n\r\n").err(),
+            Some(ParseError::MalformedHeader)
+        );
+        assert_eq!(
+            parse("GET / HTTP/1.1\r\nX: a\r\n b\x00c\r\n\r\n").err(),
+            Some(ParseError::MalformedHeader)
+        );
+    }
+
+    #[test]
+    fn refuses_malformed_fields() {
+        for field in [
+            "NoColon",
+            "Space : value",
+            ": value",
+            "X-Bad\x01: v",
+            "X: a\x00b",
+            "X: a\rb",
+        ] {
+            assert_eq!(
+                parse(&format!("GET / HTTP/1.1\r\n{field}\r\n\r\n")).err(),
+                Some(ParseError::MalformedHeader),
+                "{field:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn limits_the_header_count() {
+        let fields =
+            |count: usize| -> String { (0..count).map(|i| format!("X-{i}: v\r\n")).collect() };
+
+        let at_limit = format!("GET / HTTP/1.1\r\n{}\r\n", fields(MAX_HEADER_COUNT));
+        assert!(parse(&at_limit).is_ok());
+
+        let over = format!("GET / HTTP/1.1\r\n{}\r\n", fields(MAX_HEADER_COUNT + 1));
+        assert_eq!(parse(&over).err(), Some(ParseError::TooManyHeaders));
+    }
+
+    #[test]
+    fn limits_the_header_size() {
+        let value = "v".repeat(MAX_HEADER_SIZE);
+        assert_eq!(
+            parse(&format!("GET / HTTP/1.1\r\nX: {value}\r\n\r\n")).err(),
+            Some(ParseError::HeadersTooLarge)
+        );
+
+        // -> The limit is for the section as a whole, not per field
+        let half = "v".repeat(MAX_HEADER_SIZE / 2);
+        assert_eq!(
+            parse(&format!("GET / HTTP/1.1\r\nA: {half}\r\nB: {half}\r\n\r\n")).err(),
+            Some(ParseError::HeadersTooLarge)
+        );
+    }
+
+    #[test]
+    fn limits_the_request_line() {
+        let long = "a".repeat(MAX_REQUEST_LINE);
+        assert_eq!(
+            parse(&format!("GET /{long} HTTP/1.1\r\n\r\n")).err(),
+            Some(ParseError::URITooLong)
+        );
+        assert_eq!(
+            parse(&format!("{long}\r\n\r\n")).err(),
+            Some(ParseError::MalformedRequestLine)
+        );
+    }
+
+    #[test]
+    fn tells_closed_connections_from_truncated_requests() {
+        assert_eq!(parse("").err(), Some(ParseError::Closed));
+        assert_eq!(parse("\r\n").err(), Some(ParseError::Closed));
+        assert_eq!(parse("GET / HT").err(), Some(ParseError::Timeout));
+        assert_eq!(
+            parse("GET / HTTP/1.1\r\nHost: a").err(),
+            Some(ParseError::Timeout)
+        );
+        assert_eq!(
+            parse("GET / HTTP/1.1\r\nHost: a\r\n").err(),
+            Some(ParseError::Timeout)
+        );
+    }
+
+    fn headers(fields: &[(&str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in fields {
+            headers.append(*name, *value);
+        }
+        headers
+    }
+
+    fn framing(fields: &[(&str, &str)]) -> Result<Framing, ParseError> {
+        body_framing(&headers(fields), 100)
+    }
+
+    #[test]
+    fn frames_by_content_length() {
+        assert_eq!(framing(&[]), Ok(Framing::Empty));
+        assert_eq!(framing(&[("Content-Length", "0")]), Ok(Framing::Empty));
+        assert_eq!(
+            framing(&[("Content-Length", "42")]),
+            Ok(Framing::Length(42))
+        );
+        assert_eq!(
+            framing(&[("Content-Length", " 42 ")]),
+            Ok(Framing::Length(42))
+        );
+    }
+
+    #[test]
+    fn accepts_repeated_lengths_that_agree() {
+        assert_eq!(
+            framing(&[("Content-Length", "42, 42")]),
+            Ok(Framing::Length(42))
+        );
+        assert_eq!(
+            framing(&[("Content-Length", "42"), ("Content-Length", "42")]),
+            Ok(Framing::Length(42))
+        );
+    }
+
+    #[test]
+    fn refuses_repeated_lengths_that_disagree() {
+        assert_eq!(
+            framing(&[("Content-Length", "42, 43")]),
+            Err(ParseError::InvalidContentLength)
+        );
+        assert_eq!(
+            framing(&[("Content-Length", "42"), ("Content-Length", "43")]),
+            Err(ParseError::InvalidContentLength)
+        );
+    }
+
+    #[test]
+    fn refuses_invalid_lengths() {
+        for value in ["", "-1", "+5", "0x10", "4 2", "99999999999999999999999"] {
+            assert_eq!(
+                framing(&[("Content-Length", value)]),
+                Err(ParseError::InvalidContentLength),
+                "{value:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn refuses_lengths_over_the_limit() {
+        assert_eq!(
+            framing(&[("Content-Length", "101")]),
+            Err(ParseError::ContentTooLarge)
+        );
+    }
+
+    #[test]
+    fn frames_chunked_bodies() {
+        assert_eq!(
+            framing(&[("Transfer-Encoding", "chunked")]),
+            Ok(Framing::Chunked)
+        );
+        assert_eq!(
+            framing(&[("Transfer-Encoding", "Chunked")]),
+            Ok(Framing::Chunked)
+        );
+    }
+
+    #[test]
+    fn refuses_length_and_chunked_together() {
+        assert_eq!(
+            framing(&[("Transfer-Encoding", "chunked"), ("Content-Length", "5")]),
+            Err(ParseError::InvalidContentLength)
+        );
+        assert_eq!(
+            framing(&[("Content-Length", "5"), ("Transfer-Encoding", "chunked")]),
+            Err(ParseError::InvalidContentLength)
+        );
+    }
+
+    #[test]
+    fn refuses_other_transfer_codings() {
+        for value in ["gzip", "gzip, chunked", "chunked, chunked", "identity", ""] {
+            assert_eq!(
+                framing(&[("Transfer-Encoding", value)]),
+                Err(ParseError::UnsupportedTransferEncoding),
+                "{value:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+    },
+    thread,
+};
+
+use crate::{
+    HTTPResponse, LogLevel,
+    config::ServerConfig,
+    log,
+    status::{HTTPStatusCode, ServerErrorCode},
+    stream::Stream,
+};
+
+type ConnectionHandler = dyn Fn(Stream) + Send + Sync + 'static;
+
+pub struct WorkerPool {
+    workers: Vec<Worker>,
+    sender: Option<SyncSender<Stream>>,
+}
+
+impl WorkerPool {
+    pub fn new<F>(size: usize, queue_size: usize, handler: F) -> WorkerPool
+    where
+        F: Fn(Stream) + Send + Sync + 'static,
+    {
+        let size = size.max(1);
+
+        // -> Bounded queue between the accept loop and the workers
+        let (sender, receiver) = mpsc::sync_channel(queue_size);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let handler: Arc<ConnectionHandler> = Arc::new(handler);
+
+        let mut workers = Vec::with_capacity(size);
+
+        for id in 0..size {
+            workers.push(Worker::new(id, Arc::clone(&receiver), Arc::clone(&handler)));
+        }
+
+        WorkerPool {
+            workers,
+            sender: Some(sender),
+        }
+    }
+
+    pub fn from_config<F>(config: &ServerConfig, handler: F) -> WorkerPool
+    where
+        F: Fn(Stream) + Send + Sync + 'static,
+    {
+        WorkerPool::new(config.threads, config.queue_size, handler)
+    }
+
+    pub fn execute(&self, stream: Stream) {
+        let sender = match &self.sender {
+            Some(s) => s,
+            None => return reject(stream),
+        };
+
+        match sender.try_send(stream) {
+            Ok(_) => (),
+            Err(TrySendError::Full(stream)) => {
+                log(LogLevel::Warn, String::from("Connection queue is full"));
+                reject(stream);
+            }
+            Err(TrySendError::Disconnected(stream)) => reject(stream),
+        }
+    }
+
+    pub fn shutdown(&mut self) {
+        // -> Closing the channel lets every worker finish its current and queued connections
+        drop(self.sender.take());
+
+        for worker in &mut self.workers {
+            if let Some(thread) = worker.thread.take()
+                && thread.join().is_err()
+            {
+                log(
+                    LogLevel::Error,
+                    format!("Worker {} stopped unexpectedly", worker.id),
+                );
+            }
+        }
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+struct Worker {
+    id: usize,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Worker {
+    fn new(
+        id: usize,
+        receiver: Arc<Mutex<Receiver<Stream>>>,
+        handler: Arc<ConnectionHandler>,
+    ) -> Worker {
+        let thread = thread::spawn(move || {
+            loop {
+                let message = match receiver.lock() {
+                    Ok(r) => r.recv(),
+                    Err(_) => break,
+                };
+
+                let stream = match message {
+                    Ok(s) => s,
+                    Err(_) => break,
+                };
+
+                // -> A panicking request must not take the worker down with it
+                if panic::catch_unwind(AssertUnwindSafe(|| handler(stream))).is_err() {
+                    log(
+                        LogLevel::Error,
+                        format!("Worker {id} recovered from a panic"),
+                    );
+                }
+            }
+        });
+
+        Worker {
+            id,
+            thread: Some(thread),
+        }
+    }
+}
+
+fn reject(stream: Stream) {
+    // -> A TLS client would need a handshake first, that is not worth it for a refusal
+    let mut stream = match stream {
+        Stream::Plain(s) => s,
+        Stream::Tls(_) => return,
+    };
+
+    let mut response = HTTPResponse::builder()
+        .status(HTTPStatusCode::ServerError(
+            ServerErrorCode::ServiceUnavailable,
+        ))
+        .header("Connection", "close")
+        .build();
+
+    let _ = response.write_to(&mut stream);
+}
@@ -0,0 +1,303 @@
+use std::{
+    fs::File,
+    io,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    HTTPResponse,
+    body::Body,
+    defaults::MAX_RANGES,
+    status::{ClientErrorCode, HTTPStatusCode, SuccessCode},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub start: u64,
+    // -> Inclusive, like in Content-Range
+    pub end: u64,
+}
+
+impl ByteRange {
+    pub fn length(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    fn content_range(&self, total: u64) -> String {
+        format!("bytes {}-{}/{}", self.start, self.end, total)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RangeRequest {
+    // -> Missing, malformed or not worth honoring, the full representation is sent
+    Ignore,
+    Unsatisfiable,
+    Ranges(Vec<ByteRange>),
+}
+
+// -> RFC 9110 14.1.2, "bytes=0-99,200-,-500" against a representation of `length` bytes
+pub fn parse(value: &str, length: u64) -> RangeRequest {
+    let specs = match value.trim().split_once('=') {
+        Some((unit, specs)) if unit.trim().eq_ignore_ascii_case("bytes") => specs,
+        _ => return RangeRequest::Ignore,
+    };
+
+    let mut ranges: Vec<ByteRange> = Vec::new();
+
+    for spec in specs.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let (first, last) = match spec.split_once('-') {
+            Some(parts) => parts,
+            None => return RangeRequest::Ignore,
+        };
+
+        let range = match (first.trim(), last.trim()) {
+            // -> "-500", the last 500 bytes
+            ("", suffix) => match suffix.parse::<u64>() {
+                Ok(0) => continue,
+                Ok(n) if length > 0 => ByteRange {
+                    start: length.saturating_sub(n),
+                    end: length - 1,
+                },
+                Ok(_) => continue,
+                Err(_) => return RangeRequest::Ignore,
+            },
+            (start, end) => {
+                let start = match start.parse::<u64>() {
+                    Ok(s) => s,
+                    Err(_) => return RangeRequest::Ignore,
+                };
+
+                let end = match end {
+                    "" => u64::MAX,
+                    e => match e.parse::<u64>() {
+                        Ok(e) if e >= start => e,
+                        _ => return RangeRequest::Ignore,
+                    },
+                };
+
+                if start >= length {
+                    continue;
+                }
+
+                ByteRange {
+                    start,
+                    end: end.min(length - 1),
+                }
+            }
+        };
+
+        ranges.push(range);
+    }
+
+    if ranges.is_empty() {
+        return RangeRequest::Unsatisfiable;
+    }
+
+    // -> Overlapping or adjacent ranges are sent once, in order
+    ranges.sort_by_key(|r| r.start);
+    let mut merged: Vec<ByteRange> = Vec::with_capacity(ranges.len());
+
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end.saturating_add(1) => {
+                last.end = last.end.max(range.end);
+            }
+            _ => merged.push(range),
+        }
+    }
+
+    // -> Many tiny ranges cost more than the whole file, that's not worth honoring
+    match merged.len() > MAX_RANGES {
+        true => RangeRequest::Ignore,
+        false => RangeRequest::Ranges(merged),
+    }
+}
+
+pub fn not_satisfiable(length: u64) -> HTTPResponse {
+    HTTPResponse::builder()
+        .status(HTTPStatusCode::ClientError(
+            ClientErrorCode::RangeNotSatisfiable,
+        ))
+        .header("Content-Range", format!("bytes */{length}"))
+        .header("Accept-Ranges", "bytes")
+        .build()
+}
+
+pub fn partial(
+    file: File,
+    length: u64,
+    content_type: &str,
+    ranges: &[ByteRange],
+) -> io::Result<HTTPResponse> {
+    let builder = HTTPResponse::builder()
+        .status(HTTPStatusCode::Success(SuccessCode::PartialContent))
+        .header("Accept-Ranges", "bytes");
+
+    if let [range] = ranges {
+        return Ok(builder
+            .header("Content-Type", content_type)
+            .header("Content-Range", range.content_range(length))
+            .body(Body::Range(file, range.start, range.length()))
+            .build());
+    }
+
+    let boundary = boundary();
+    let mut parts: Vec<Body> = Vec::with_capacity(ranges.len() * 2 + 1);
+
+    for range in ranges {
+        parts.push(Body::from(format!(
+            "\r\n--{boundary}\r\nContent-Type: {content_type}\r\nContent-Range: {}\r\n\r\n",
+            range.content_range(length)
+        )));
+        parts.push(Body::Range(file.try_clone()?, range.start, range.length()));
+    }
+
+    parts.push(Body::from(format!("\r\n--{boundary}--\r\n")));
+
+    Ok(builder
+        .header(
+            "Content-Type",
+            format!("multipart/byteranges; boundary={boundary}"),
+        )
+        .body(Body::Parts(parts))
+        .build())
+}
+
+// -> Only has to be absent from the file parts, time and a counter are plenty
+fn boundary() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let nanos = match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_nanos() as u64,
+        Err(_) => 0,
+    };
+
+    format!(
+        "rws-{:016x}{:08x}",
+        nanos,
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges(pairs: &[(u64, u64)]) -> RangeRequest {
+        RangeRequest::Ranges(
+            pairs
+                .iter()
+                .map(|(start, end)| ByteRange {
+                    start: *start,
+                    end: *end,
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn parses_closed_ranges() {
+        assert_eq!(parse("bytes=0-99", 1000), ranges(&[(0, 99)]));
+        assert_eq!(parse("bytes=500-500", 1000), ranges(&[(500, 500)]));
+        assert_eq!(parse(" Bytes = 10 - 19 ", 1000), ranges(&[(10, 19)]));
+    }
+
+    #[test]
+    fn clamps_ends_past_the_length() {
+        assert_eq!(parse("bytes=900-5000", 1000), ranges(&[(900, 999)]));
+    }
+
+    #[test]
+    fn parses_open_ended_ranges() {
+        assert_eq!(parse("bytes=200-", 1000), ranges(&[(200, 999)]));
+        assert_eq!(parse("bytes=0-", 1), ranges(&[(0, 0)]));
+    }
+
+    #[test]
+    fn parses_suffix_ranges() {
+        assert_eq!(parse("bytes=-500", 1000), ranges(&[(500, 999)]));
+        // -> A suffix longer than the representation is the whole of it
+        assert_eq!(parse("bytes=-5000", 1000), ranges(&[(0, 999)]));
+    }
+
+    #[test]
+    fn merges_overlapping_and_adjacent_ranges() {
+        assert_eq!(parse("bytes=0-99,50-149", 1000), ranges(&[(0, 149)]));
+        assert_eq!(parse("bytes=0-99,100-199", 1000), ranges(&[(0, 199)]));
+        assert_eq!(
+            parse("bytes=500-599,0-99,-100", 1000),
+            ranges(&[(0, 99), (500, 599), (900, 999)])
+        );
+        assert_eq!(parse("bytes=0-9,20-29,5-24", 1000), ranges(&[(0, 29)]));
+        assert_eq!(
+            parse("bytes=0-99,101-199", 1000),
+            ranges(&[(0, 99), (101, 199)])
+        );
+    }
+
+    #[test]
+    fn answers_416_when_nothing_is_satisfiable() {
+        assert_eq!(parse("bytes=1000-", 1000), RangeRequest::Unsatisfiable);
+        assert_eq!(parse("bytes=1000-2000", 1000), RangeRequest::Unsatisfiable);
+        assert_eq!(parse("bytes=-0", 1000), RangeRequest::Unsatisfiable);
+        assert_eq!(parse("bytes=0-", 0), RangeRequest::Unsatisfiable);
+        assert_eq!(parse("bytes=-10", 0), RangeRequest::Unsatisfiable);
+    }
+
+    #[test]
+    fn keeps_satisfiable_ranges_next_to_unsatisfiable_ones() {
+        assert_eq!(parse("bytes=2000-,0-9", 1000), ranges(&[(0, 9)]));
+    }
+
+    #[test]
+    fn ignores_invalid_specs() {
+        for value in [
+            "",
+            "bytes",
+            "items=0-9",
+            "bytes=0-9,abc",
+            "bytes=9-0",
+            "bytes=a-9",
+            "bytes=0-b",
+            "bytes=--5",
+            "bytes=5",
+            "bytes=-x",
+        ] {
+            assert_eq!(parse(value, 1000), RangeRequest::Ignore, "{value:?}");
+        }
+    }
+
+    #[test]
+    fn ignores_too_many_ranges() {
+        let at_limit: Vec<String> = (0..MAX_RANGES as u64)
+            .map(|i| format!("{}-{}", i * 10, i * 10 + 1))
+            .collect();
+        match parse(&format!("bytes={}", at_limit.join(",")), 10_000) {
+            RangeRequest::Ranges(r) => assert_eq!(r.len(), MAX_RANGES),
+            other => panic!("expected ranges, got {other:?}"),
+        }
+
+        let over: Vec<String> = (0..=MAX_RANGES as u64)
+            .map(|i| format!("{}-{}", i * 10, i * 10 + 1))
+            .collect();
+        assert_eq!(
+            parse(&format!("bytes={}", over.join(",")), 10_000),
+            RangeRequest::Ignore
+        );
+    }
+
+    #[test]
+    fn counts_ranges_after_merging() {
+        // -> Many requested ranges that merge into one are fine
+        let spec: Vec<String> = (0..=MAX_RANGES as u64)
+            .map(|i| format!("{}-{}", i * 10, i * 10 + 9))
+            .collect();
+        let end = (MAX_RANGES as u64) * 10 + 9;
+        assert_eq!(
+            parse(&format!("bytes={}", spec.join(",")), 10_000),
+            ranges(&[(0, end)])
+        );
+    }
+}
@@ -0,0 +1,191 @@
+use std::{
+    fmt, fs, io,
+    path::{Component, Path, PathBuf},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    LogLevel, RequestURL,
+    config::ServerConfig,
+    defaults::SERVE_HIDDEN,
+    log,
+    status::{ClientErrorCode, HTTPStatusCode, ServerErrorCode},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SymlinkPolicy {
+    // -> Never serve anything reached through a symlink
+    Deny,
+    // -> Follow symlinks as long as the target stays inside the root
+    #[default]
+    #[serde(alias = "within_root")]
+    WithinRoot,
+    // -> Follow symlinks anywhere, the root check is skipped
+    Follow,
+}
+
+impl FromStr for SymlinkPolicy {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<SymlinkPolicy, String> {
+        match input.to_ascii_lowercase().as_str() {
+            "deny" => Ok(SymlinkPolicy::Deny),
+            "within-root" | "within_root" => Ok(SymlinkPolicy::WithinRoot),
+            "follow" => Ok(SymlinkPolicy::Follow),
+            s => Err(String::from(s)),
+        }
+    }
+}
+
+impl fmt::Display for SymlinkPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SymlinkPolicy::Deny => write!(f, "deny"),
+            SymlinkPolicy::WithinRoot => write!(f, "within-root"),
+            SymlinkPolicy::Follow => write!(f, "follow"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Resolver {
+    root: PathBuf,
+    symlinks: SymlinkPolicy,
+    serve_hidden: bool,
+}
+
+impl Resolver {
+    pub fn new(root: impl Into<PathBuf>) -> Resolver {
+        Resolver {
+            root: root.into(),
+            symlinks: SymlinkPolicy::default(),
+            serve_hidden: SERVE_HIDDEN,
+        }
+    }
+
+    pub fn from_config(config: &ServerConfig) -> Resolver {
+        Resolver::new(&config.root)
+            .symlinks(config.symlinks)
+            .serve_hidden(config.serve_hidden)
+    }
+
+    pub fn symlinks(mut self, policy: SymlinkPolicy) -> Resolver {
+        self.symlinks = policy;
+        self
+    }
+
+    pub fn serve_hidden(mut self, serve_hidden: bool) -> Resolver {
+        self.serve_hidden = serve_hidden;
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn resolve(&self, url: &RequestURL) -> Result<PathBuf, HTTPStatusCode> {
+        self.check(&self.candidate(url)?)
+    }
+
+    pub fn is_hidden(&self, name: &str) -> bool {
+        !self.serve_hidden && name.starts_with('.')
+    }
+
+    // -> Unresolved location of a URL below the root, before any symlink is followed
+    pub fn candidate(&self, url: &RequestURL) -> Result<PathBuf, HTTPStatusCode> {
+        // -> Dotfiles are reported as missing so their existence doesn't leak
+        if url.segments().iter().any(|s| self.is_hidden(s)) {
+            return Err(HTTPStatusCode::ClientError(ClientErrorCode::NotFound));
+        }
+
+        let mut candidate = self.root.clone();
+
+        for segment in url.segments() {
+            candidate.push(segment);
+        }
+
+        Ok(candidate)
+    }
+
+    pub fn check(&self, candidate: &Path) -> Result<PathBuf, HTTPStatusCode> {
+        let root = match self.root.canonicalize() {
+            Ok(r) => r,
+            Err(e) => {
+                log(
+                    LogLevel::Error,
+                    format!(
+                        "Root directory \"{}\" is unusable: {}",
+                        self.root.display(),
+                        e
+                    ),
+                );
+                return Err(HTTPStatusCode::ServerError(
+                    ServerErrorCode::InternalServerError,
+                ));
+            }
+        };
+
+        // -> Only plain names may follow the root, nothing that could climb out of it
+        match candidate.strip_prefix(&self.root) {
+            Ok(rest) if rest.components().all(|c| matches!(c, Component::Normal(_))) => (),
+            _ => return Err(HTTPStatusCode::ClientError(ClientErrorCode::BadRequest)),
+        }
+
+        if self.symlinks == SymlinkPolicy::Deny && contains_symlink(&self.root, candidate)? {
+            log(
+                LogLevel::Warn,
+                format!("Refusing symlink in \"{}\"", candidate.display()),
+            );
+            return Err(HTTPStatusCode::ClientError(ClientErrorCode::Forbidden));
+        }
+
+        let resolved = match candidate.canonicalize() {
+            Ok(p) => p,
+            Err(e) => return Err(io_status(e)),
+        };
+
+        if self.symlinks != SymlinkPolicy::Follow && !resolved.starts_with(&root) {
+            log(
+                LogLevel::Warn,
+                format!(
+                    "Refusing \"{}\", it resolves outside the root",
+                    candidate.display()
+                ),
+            );
+            return Err(HTTPStatusCode::ClientError(ClientErrorCode::Forbidden));
+        }
+
+        Ok(resolved)
+    }
+}
+
+fn contains_symlink(root: &Path, candidate: &Path) -> Result<bool, HTTPStatusCode> {
+    let rest = match candidate.strip_prefix(root) {
+        Ok(r) => r,
+        Err(_) => return Ok(true),
+    };
+
+    let mut current = root.to_path_buf();
+
+    for component in rest.components() {
+        current.push(component);
+
+        match fs::symlink_metadata(&current) {
+            Ok(meta) if meta.file_type().is_symlink() => return Ok(true),
+            Ok(_) => (),
+            Err(e) => return Err(io_status(e)),
+        }
+    }
+
+    Ok(false)
+}
+
+fn io_status(error: io::Error) -> HTTPStatusCode {
+    match error.kind() {
+        io::ErrorKind::PermissionDenied => HTTPStatusCode::ClientError(ClientErrorCode::Forbidden),
+        _ => HTTPStatusCode::ClientError(ClientErrorCode::NotFound),
+    }
+}
@@ -0,0 +1,440 @@
+use std::{cmp::Ordering, collections::HashMap, sync::Arc};
+
+use crate::{
+    HTTPMethod, HTTPRequest, HTTPResponse, files,
+    handler::{Handler, Middleware, Stack, merge_methods},
+    status::{ClientErrorCode, HTTPStatusCode, SuccessCode},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Static(String),
+    Param(String),
+    Wildcard(String),
+}
+
+impl Segment {
+    // -> Match priority, static beats param beats wildcard
+    fn rank(&self) -> u8 {
+        match self {
+            Segment::Static(_) => 0,
+            Segment::Param(_) => 1,
+            Segment::Wildcard(_) => 2,
+        }
+    }
+}
+
+struct Route {
+    method: HTTPMethod,
+    pattern: Vec<Segment>,
+    handler: Arc<dyn Handler>,
+}
+
+pub struct Router {
+    routes: Vec<Route>,
+    fallback: Arc<dyn Handler>,
+}
+
+impl Router {
+    pub fn new() -> Router {
+        Router {
+            routes: Vec::new(),
+            fallback: Arc::new(files::serve),
+        }
+    }
+
+    // -> "/users/:id/files/*path", a wildcard anywhere but the last segment panics
+    pub fn route<H: Handler + 'static>(
+        mut self,
+        method: HTTPMethod,
+        pattern: &str,
+        handler: H,
+    ) -> Router {
+        self.routes.push(Route {
+            method,
+            pattern: parse_pattern(pattern),
+            handler: Arc::new(handler),
+        });
+        self
+    }
+
+    pub fn get<H: Handler + 'static>(self, pattern: &str, handler: H) -> Router {
+        self.route(HTTPMethod::GET, pattern, handler)
+    }
+
+    pub fn post<H: Handler + 'static>(self, pattern: &str, handler: H) -> Router {
+        self.route(HTTPMethod::POST, pattern, handler)
+    }
+
+    pub fn put<H: Handler + 'static>(self, pattern: &str, handler: H) -> Router {
+        self.route(HTTPMethod::PUT, pattern, handler)
+    }
+
+    pub fn patch<H: Handler + 'static>(self, pattern: &str, handler: H) -> Router {
+        self.route(HTTPMethod::PATCH, pattern, handler)
+    }
+
+    pub fn delete<H: Handler + 'static>(self, pattern: &str, handler: H) -> Router {
+        self.route(HTTPMethod::DELETE, pattern, handler)
+    }
+
+    pub fn fallback<H: Handler + 'static>(mut self, handler: H) -> Router {
+        self.fallback = Arc::new(handler);
+        self
+    }
+
+    // -> Runs middleware around this router only, e.g. auth for an API router
+    pub fn wrap<M: Middleware + 'static>(self, middleware: M) -> Stack {
+        Stack::new(self).wrap(middleware)
+    }
+}
+
+impl Handler for Router {
+    fn handle(&self, request: &mut HTTPRequest) -> HTTPResponse {
+        let url = match request.url() {
+            Ok(u) => u,
+            Err(code) => return HTTPResponse::builder().status(code).build(),
+        };
+
+        let mut best: Option<(&Route, HashMap<String, String>)> = None;
+        let mut allowed: Vec<HTTPMethod> = Vec::new();
+
+        for route in &self.routes {
+            let params = match match_pattern(&route.pattern, url.segments()) {
+                Some(p) => p,
+                None => continue,
+            };
+
+            if !allowed.contains(&route.method) {
+                allowed.push(route.method);
+            }
+
+            // -> HEAD is answered by the GET route unless it has one of its own
+            let exact = route.method == request.method;
+            let head = request.method == HTTPMethod::HEAD && route.method == HTTPMethod::GET;
+            if !(exact || head) {
+                continue;
+            }
+
+            // -> Most specific pattern wins, then an exact method, ties go to the route registered first
+            let better = match &best {
+                Some((current, _)) => {
+                    let ranks = route.pattern.iter().map(Segment::rank);
+                    match ranks.cmp(current.pattern.iter().map(Segment::rank)) {
+                        Ordering::Less => true,
+                        Ordering::Equal => exact && current.method != request.method,
+                        Ordering::Greater => false,
+                    }
+                }
+                None => true,
+            };
+
+            if better {
+                best = Some((route, params));
+            }
+        }
+
+        match best {
+            Some((route, params)) => {
+                request.params = params;
+                route.handler.handle(request)
+            }
+            None if request.method == HTTPMethod::OPTIONS && !allowed.is_empty() => {
+                options(&allowed)
+            }
+            None if !allowed.is_empty() => method_not_allowed(&allowed),
+            None => self.fallback.handle(request),
+        }
+    }
+
+    fn methods(&self) -> Vec<HTTPMethod> {
+        let mut methods: Vec<HTTPMethod> = Vec::new();
+        merge_methods(&mut methods, self.routes.iter().map(|r| r.method).collect());
+        merge_methods(&mut methods, self.fallback.methods());
+        methods
+    }
+}
+
+impl Default for Router {
+    fn default() -> Self {
+        Router::new()
+    }
+}
+
+pub fn method_not_allowed(allowed: &[HTTPMethod]) -> HTTPResponse {
+    HTTPResponse::builder()
+        .status(HTTPStatusCode::ClientError(
+            ClientErrorCode::MethodNotAllowed,
+        ))
+        .header("Allow", allow_header(allowed))
+        .build()
+}
+
+pub fn options(allowed: &[HTTPMethod]) -> HTTPResponse {
+    HTTPResponse::builder()
+        .status(HTTPStatusCode::Success(SuccessCode::NoContent))
+        .header("Allow", allow_header(allowed))
+        .build()
+}
+
+// -> GET implies HEAD, and OPTIONS is always answered
+pub fn allow_header(allowed: &[HTTPMethod]) -> String {
+    let mut methods: Vec<HTTPMethod> = Vec::with_capacity(allowed.len() + 2);
+
+    for method in allowed {
+        if !methods.contains(method) {
+            methods.push(*method);
+        }
+
+        if *method == HTTPMethod::GET && !allowed.contains(&HTTPMethod::HEAD) {
+            methods.push(HTTPMethod::HEAD);
+        }
+    }
+
+    if !methods.contains(&HTTPMethod::OPTIONS) {
+        methods.push(HTTPMethod::OPTIONS);
+    }
+
+    let names: Vec<String> = methods.iter().map(|m| m.to_string()).collect();
+    names.join(", ")
+}
+
+fn parse_pattern(pattern: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+
+    for part in pattern.split('/').filter(|p| !p.is_empty()) {
+        if let Some(Segment::Wildcard(_)) = segments.last() {
+            panic!("\"{pattern}\": a wildcard has to be the last segment");
+        }
+
+        if let Some(name) = part.strip_prefix(':') {
+            segments.push(Segment::Param(String::from(name)));
+        } else if let Some(name) = part.strip_prefix('*') {
+            segments.push(Segment::Wildcard(String::from(name)));
+        } else {
+            segments.push(Segment::Static(String::from(part)));
+        }
+    }
+
+    segments
+}
+
+fn match_pattern(pattern: &[Segment], path: &[String]) -> Option<HashMap<String, String>> {
+    let mut params = HashMap::new();
+
+    for (i, segment) in pattern.iter().enumerate() {
+        match segment {
+            Segment::Static(s) => {
+                if path.get(i) != Some(s) {
+                    return None;
+                }
+            }
+            Segment::Param(name) => {
+                params.insert(name.clone(), path.get(i)?.clone());
+            }
+            Segment::Wildcard(name) => {
+                // -> Takes the rest of the path, possibly nothing
+                let rest = path.get(i..).unwrap_or_default();
+                params.insert(name.clone(), rest.join("/"));
+                return Some(params);
+            }
+        }
+    }
+
+    match pattern.len() == path.len() {
+        true => Some(params),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    use crate::parser::parse_request;
+
+    fn named(name: &'static str) -> impl Handler {
+        move |request: &mut HTTPRequest| {
+            let mut params: Vec<String> = request
+                .params
+                .iter()
+                .map(|(k, v)| format!("{k}={v}"))
+                .collect();
+            params.sort();
+
+            HTTPResponse::builder()
+                .header("X-Route", name)
+                .header("X-Params", params.join("&"))
+                .build()
+        }
+    }
+
+    fn router() -> Router {
+        Router::new().fallback(named("fallback"))
+    }
+
+    fn send(router: &Router, method: &str, path: &str) -> HTTPResponse {
+        let input = format!("{method} {path} HTTP/1.1\r\nHost: a.test\r\n\r\n");
+        let mut request = parse_request(&mut Cursor::new(input.into_bytes())).unwrap();
+        router.handle(&mut request)
+    }
+
+    fn route(router: &Router, method: &str, path: &str) -> (String, String) {
+        let response = send(router, method, path);
+        (
+            String::from(response.headers.get("X-Route").unwrap_or("")),
+            String::from(response.headers.get("X-Params").unwrap_or("")),
+        )
+    }
+
+    fn pair(route: &str, params: &str) -> (String, String) {
+        (String::from(route), String::from(params))
+    }
+
+    #[test]
+    fn captures_params() {
+        let router = router().get("/users/:id/posts/:post", named("post"));
+
+        assert_eq!(
+            route(&router, "GET", "/users/7/posts/42"),
+            pair("post", "id=7&post=42")
+        );
+        assert_eq!(
+            route(&router, "GET", "/users/7/posts"),
+            pair("fallback", "")
+        );
+        assert_eq!(
+            route(&router, "GET", "/users/7/posts/42/x"),
+            pair("fallback", "")
+        );
+    }
+
+    #[test]
+    fn captures_the_rest_of_the_path_with_a_wildcard() {
+        let router = router().get("/files/*path", named("files"));
+
+        assert_eq!(
+            route(&router, "GET", "/files/a/b/c.txt"),
+            pair("files", "path=a/b/c.txt")
+        );
+        assert_eq!(route(&router, "GET", "/files/a"), pair("files", "path=a"));
+        assert_eq!(route(&router, "GET", "/files"), pair("files", "path="));
+        assert_eq!(route(&router, "GET", "/other"), pair("fallback", ""));
+    }
+
+    #[test]
+    fn static_beats_param_beats_wildcard() {
+        // -> Registered least specific first, so the order doesn't decide
+        let router = router()
+            .get("/users/*rest", named("wildcard"))
+            .get("/users/:id", named("param"))
+            .get("/users/me", named("static"));
+
+        assert_eq!(route(&router, "GET", "/users/me"), pair("static", ""));
+        assert_eq!(route(&router, "GET", "/users/7"), pair("param", "id=7"));
+        assert_eq!(
+            route(&router, "GET", "/users/7/x"),
+            pair("wildcard", "rest=7/x")
+        );
+    }
+
+    #[test]
+    fn earlier_segments_weigh_more() {
+        let router = router()
+            .get("/:a/static", named("param-first"))
+            .get("/static/:b", named("static-first"));
+
+        assert_eq!(
+            route(&router, "GET", "/static/static"),
+            pair("static-first", "b=static")
+        );
+    }
+
+    #[test]
+    fn ties_go_to_the_route_registered_first() {
+        let router = router()
+            .get("/items/:id", named("first"))
+            .get("/items/:name", named("second"));
+
+        assert_eq!(route(&router, "GET", "/items/1"), pair("first", "id=1"));
+    }
+
+    #[test]
+    fn head_uses_the_get_route_unless_it_has_its_own() {
+        let router = router().get("/a", named("get-a")).get("/b", named("get-b"));
+        let router = router.route(HTTPMethod::HEAD, "/b", named("head-b"));
+
+        assert_eq!(route(&router, "HEAD", "/a").0, "get-a");
+        assert_eq!(route(&router, "HEAD", "/b").0, "head-b");
+    }
+
+    #[test]
+    fn answers_405_with_the_allowed_methods() {
+        let router = router()
+            .get("/items/:id", named("get"))
+            .delete("/items/:id", named("delete"))
+            .post("/items", named("create"));
+
+        let response = send(&router, "PUT", "/items/1");
+        assert_eq!(response.status.to_value(), 405);
+        assert_eq!(
+            response.headers.get("Allow"),
+            Some("GET, HEAD, DELETE, OPTIONS")
+        );
+
+        let response = send(&router, "GET", "/items");
+        assert_eq!(response.status.to_value(), 405);
+        assert_eq!(response.headers.get("Allow"), Some("POST, OPTIONS"));
+
+        // -> Nothing matches the path, so the method isn't the problem
+        assert_eq!(route(&router, "PUT", "/other"), pair("fallback", ""));
+    }
+
+    #[test]
+    fn answers_options_for_a_matched_path() {
+        let router = router()
+            .get("/items", named("get"))
+            .post("/items", named("create"));
+
+        let response = send(&router, "OPTIONS", "/items");
+        assert_eq!(response.status.to_value(), 204);
+        assert_eq!(
+            response.headers.get("Allow"),
+            Some("GET, HEAD, POST, OPTIONS")
+        );
+    }
+
+    #[test]
+    fn builds_allow_headers() {
+        assert_eq!(allow_header(&[HTTPMethod::GET]), "GET, HEAD, OPTIONS");
+        assert_eq!(
+            allow_header(&[HTTPMethod::HEAD, HTTPMethod::GET, HTTPMethod::GET]),
+            "HEAD, GET, OPTIONS"
+        );
+        assert_eq!(
+            allow_header(&[HTTPMethod::OPTIONS, HTTPMethod::POST]),
+            "OPTIONS, POST"
+        );
+    }
+
+    #[test]
+    fn parses_patterns() {
+        assert_eq!(
+            parse_pattern("/users//:id/*rest/"),
+            vec![
+                Segment::Static(String::from("users")),
+                Segment::Param(String::from("id")),
+                Segment::Wildcard(String::from("rest")),
+            ]
+        );
+        assert_eq!(parse_pattern("/"), Vec::new());
+    }
+
+    #[test]
+    #[should_panic(expected = "a wildcard has to be the last segment")]
+    fn refuses_a_wildcard_before_the_last_segment() {
+        let _ = router().get("/files/*path/edit", named("edit"));
+    }
+}
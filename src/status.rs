@@ -1,6 +1,6 @@
 use std::fmt;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum HTTPStatusCode {
     // https://developer.mozilla.org/en-US/docs/Web/HTTP/Reference/Status
     Informal(InformalCode),
@@ -10,7 +10,7 @@ pub enum HTTPStatusCode {
     ServerError(ServerErrorCode),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum InformalCode {
     Continue,
     SwitchingProtocols,
@@ -18,7 +18,7 @@ pub enum InformalCode {
     EarlyHints,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum SuccessCode {
     OK,
     Created,
@@ -32,7 +32,7 @@ pub enum SuccessCode {
     IMUsed,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum RedirectionCode {
     MultipleChoices,
     MovedPermanently,
@@ -41,9 +41,10 @@ pub enum RedirectionCode {
     NotModified,
     UseProxy,
     TemporaryRedirect,
+    PermanentRedirect,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ClientErrorCode {
     BadRequest,
     Unauthorized,
@@ -76,7 +77,7 @@ pub enum ClientErrorCode {
     UnavailableForLegalReasons,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ServerErrorCode {
     InternalServerError,
     NotImplemented,
@@ -95,154 +96,259 @@ impl HTTPStatusCode {
     pub fn to_value(&self) -> u16 {
         match self {
             HTTPStatusCode::Informal(code) => match code {
-                InformalCode::Continue => todo!(),
-                InformalCode::SwitchingProtocols => todo!(),
-                InformalCode::Processing => todo!(),
-                InformalCode::EarlyHints => todo!(),
+                InformalCode::Continue => 100,
+                InformalCode::SwitchingProtocols => 101,
+                InformalCode::Processing => 102,
+                InformalCode::EarlyHints => 103,
             },
             HTTPStatusCode::Success(code) => match code {
                 SuccessCode::OK => 200,
-                SuccessCode::Created => todo!(),
-                SuccessCode::Accepted => todo!(),
-                SuccessCode::NonAuthorativeInformation => todo!(),
-                SuccessCode::NoContent => todo!(),
-                SuccessCode::ResetContent => todo!(),
-                SuccessCode::PartialContent => todo!(),
-                SuccessCode::MultiStatus => todo!(),
-                SuccessCode::AlreadyReported => todo!(),
-                SuccessCode::IMUsed => todo!(),
+                SuccessCode::Created => 201,
+                SuccessCode::Accepted => 202,
+                SuccessCode::NonAuthorativeInformation => 203,
+                SuccessCode::NoContent => 204,
+                SuccessCode::ResetContent => 205,
+                SuccessCode::PartialContent => 206,
+                SuccessCode::MultiStatus => 207,
+                SuccessCode::AlreadyReported => 208,
+                SuccessCode::IMUsed => 226,
             },
             HTTPStatusCode::Redirection(code) => match code {
-                RedirectionCode::MultipleChoices => todo!(),
-                RedirectionCode::MovedPermanently => todo!(),
-                RedirectionCode::Found => todo!(),
-                RedirectionCode::SeeOther => todo!(),
-                RedirectionCode::NotModified => todo!(),
-                RedirectionCode::UseProxy => todo!(),
-                RedirectionCode::TemporaryRedirect => todo!(),
+                RedirectionCode::MultipleChoices => 300,
+                RedirectionCode::MovedPermanently => 301,
+                RedirectionCode::Found => 302,
+                RedirectionCode::SeeOther => 303,
+                RedirectionCode::NotModified => 304,
+                RedirectionCode::UseProxy => 305,
+                RedirectionCode::TemporaryRedirect => 307,
+                RedirectionCode::PermanentRedirect => 308,
             },
             HTTPStatusCode::ClientError(code) => match code {
-                ClientErrorCode::BadRequest => todo!(),
-                ClientErrorCode::Unauthorized => todo!(),
-                ClientErrorCode::PaymentRequired => todo!(),
-                ClientErrorCode::Forbidden => todo!(),
+                ClientErrorCode::BadRequest => 400,
+                ClientErrorCode::Unauthorized => 401,
+                ClientErrorCode::PaymentRequired => 402,
+                ClientErrorCode::Forbidden => 403,
                 ClientErrorCode::NotFound => 404,
-                ClientErrorCode::MethodNotAllowed => todo!(),
-                ClientErrorCode::NotAcceptable => todo!(),
-                ClientErrorCode::ProxyAuthenticationRequired => todo!(),
-                ClientErrorCode::RequestTimeout => todo!(),
-                ClientErrorCode::Conflict => todo!(),
-                ClientErrorCode::Gone => todo!(),
-                ClientErrorCode::LengthRequired => todo!(),
-                ClientErrorCode::PreconditionFailed => todo!(),
-                ClientErrorCode::ContentTooLarge => todo!(),
-                ClientErrorCode::URITooLong => todo!(),
-                ClientErrorCode::UnsupportedMediaType => todo!(),
-                ClientErrorCode::RangeNotSatisfiable => todo!(),
-                ClientErrorCode::ExpectationFailed => todo!(),
-                ClientErrorCode::ImATeapot => todo!(),
-                ClientErrorCode::MisdirectedRequest => todo!(),
-                ClientErrorCode::UnprocessableContent => todo!(),
-                ClientErrorCode::Locked => todo!(),
-                ClientErrorCode::FailedDependency => todo!(),
-                ClientErrorCode::TooEarly => todo!(),
-                ClientErrorCode::UpgradeRequired => todo!(),
-                ClientErrorCode::PreconditionRequired => todo!(),
-                ClientErrorCode::TooManyRequests => todo!(),
-                ClientErrorCode::RequestHeaderFieldsTooLarge => todo!(),
-                ClientErrorCode::UnavailableForLegalReasons => todo!(),
+                ClientErrorCode::MethodNotAllowed => 405,
+                ClientErrorCode::NotAcceptable => 406,
+                ClientErrorCode::ProxyAuthenticationRequired => 407,
+                ClientErrorCode::RequestTimeout => 408,
+                ClientErrorCode::Conflict => 409,
+                ClientErrorCode::Gone => 410,
+                ClientErrorCode::LengthRequired => 411,
+                ClientErrorCode::PreconditionFailed => 412,
+                ClientErrorCode::ContentTooLarge => 413,
+                ClientErrorCode::URITooLong => 414,
+                ClientErrorCode::UnsupportedMediaType => 415,
+                ClientErrorCode::RangeNotSatisfiable => 416,
+                ClientErrorCode::ExpectationFailed => 417,
+                ClientErrorCode::ImATeapot => 418,
+                ClientErrorCode::MisdirectedRequest => 421,
+                ClientErrorCode::UnprocessableContent => 422,
+                ClientErrorCode::Locked => 423,
+                ClientErrorCode::FailedDependency => 424,
+                ClientErrorCode::TooEarly => 425,
+                ClientErrorCode::UpgradeRequired => 426,
+                ClientErrorCode::PreconditionRequired => 428,
+                ClientErrorCode::TooManyRequests => 429,
+                ClientErrorCode::RequestHeaderFieldsTooLarge => 431,
+                ClientErrorCode::UnavailableForLegalReasons => 451,
             },
             HTTPStatusCode::ServerError(code) => match code {
                 ServerErrorCode::InternalServerError => 500,
-                ServerErrorCode::NotImplemented => todo!(),
-                ServerErrorCode::BadGateway => todo!(),
-                ServerErrorCode::ServiceUnavailable => todo!(),
-                ServerErrorCode::GatewayTimeout => todo!(),
-                ServerErrorCode::HTTPVersionNotSupported => todo!(),
-                ServerErrorCode::VariantAlsoNegotiates => todo!(),
-                ServerErrorCode::InsufficientStorage => todo!(),
-                ServerErrorCode::LoopDetected => todo!(),
-                ServerErrorCode::NotExtended => todo!(),
-                ServerErrorCode::NetworkAuthenticationRequired => todo!(),
+                ServerErrorCode::NotImplemented => 501,
+                ServerErrorCode::BadGateway => 502,
+                ServerErrorCode::ServiceUnavailable => 503,
+                ServerErrorCode::GatewayTimeout => 504,
+                ServerErrorCode::HTTPVersionNotSupported => 505,
+                ServerErrorCode::VariantAlsoNegotiates => 506,
+                ServerErrorCode::InsufficientStorage => 507,
+                ServerErrorCode::LoopDetected => 508,
+                ServerErrorCode::NotExtended => 510,
+                ServerErrorCode::NetworkAuthenticationRequired => 511,
             },
         }
     }
-}
 
-impl fmt::Display for HTTPStatusCode {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    pub fn reason(&self) -> &'static str {
         match self {
             HTTPStatusCode::Informal(code) => match code {
-                InformalCode::Continue => write!(f, "Continue"),
-                InformalCode::SwitchingProtocols => todo!(),
-                InformalCode::Processing => todo!(),
-                InformalCode::EarlyHints => todo!(),
+                InformalCode::Continue => "Continue",
+                InformalCode::SwitchingProtocols => "Switching Protocols",
+                InformalCode::Processing => "Processing",
+                InformalCode::EarlyHints => "Early Hints",
             },
             HTTPStatusCode::Success(code) => match code {
-                SuccessCode::OK => write!(f, "Ok"),
-                SuccessCode::Created => todo!(),
-                SuccessCode::Accepted => todo!(),
-                SuccessCode::NonAuthorativeInformation => todo!(),
-                SuccessCode::NoContent => todo!(),
-                SuccessCode::ResetContent => todo!(),
-                SuccessCode::PartialContent => todo!(),
-                SuccessCode::MultiStatus => todo!(),
-                SuccessCode::AlreadyReported => todo!(),
-                SuccessCode::IMUsed => todo!(),
+                SuccessCode::OK => "OK",
+                SuccessCode::Created => "Created",
+                SuccessCode::Accepted => "Accepted",
+                SuccessCode::NonAuthorativeInformation => "Non-Authoritative Information",
+                SuccessCode::NoContent => "No Content",
+                SuccessCode::ResetContent => "Reset Content",
+                SuccessCode::PartialContent => "Partial Content",
+                SuccessCode::MultiStatus => "Multi-Status",
+                SuccessCode::AlreadyReported => "Already Reported",
+                SuccessCode::IMUsed => "IM Used",
             },
             HTTPStatusCode::Redirection(code) => match code {
-                RedirectionCode::MultipleChoices => todo!(),
-                RedirectionCode::MovedPermanently => todo!(),
-                RedirectionCode::Found => todo!(),
-                RedirectionCode::SeeOther => todo!(),
-                RedirectionCode::NotModified => todo!(),
-                RedirectionCode::UseProxy => todo!(),
-                RedirectionCode::TemporaryRedirect => todo!(),
+                RedirectionCode::MultipleChoices => "Multiple Choices",
+                RedirectionCode::MovedPermanently => "Moved Permanently",
+                RedirectionCode::Found => "Found",
+                RedirectionCode::SeeOther => "See Other",
+                RedirectionCode::NotModified => "Not Modified",
+                RedirectionCode::UseProxy => "Use Proxy",
+                RedirectionCode::TemporaryRedirect => "Temporary Redirect",
+                RedirectionCode::PermanentRedirect => "Permanent Redirect",
             },
             HTTPStatusCode::ClientError(code) => match code {
-                ClientErrorCode::BadRequest => todo!(),
-                ClientErrorCode::Unauthorized => todo!(),
-                ClientErrorCode::PaymentRequired => todo!(),
-                ClientErrorCode::Forbidden => todo!(),
-                ClientErrorCode::NotFound => write!(f, "Not Found"),
-                ClientErrorCode::MethodNotAllowed => todo!(),
-                ClientErrorCode::NotAcceptable => todo!(),
-                ClientErrorCode::ProxyAuthenticationRequired => todo!(),
-                ClientErrorCode::RequestTimeout => todo!(),
-                ClientErrorCode::Conflict => todo!(),
-                ClientErrorCode::Gone => todo!(),
-                ClientErrorCode::LengthRequired => todo!(),
-                ClientErrorCode::PreconditionFailed => todo!(),
-                ClientErrorCode::ContentTooLarge => todo!(),
-                ClientErrorCode::URITooLong => todo!(),
-                ClientErrorCode::UnsupportedMediaType => todo!(),
-                ClientErrorCode::RangeNotSatisfiable => todo!(),
-                ClientErrorCode::ExpectationFailed => todo!(),
-                ClientErrorCode::ImATeapot => todo!(),
-                ClientErrorCode::MisdirectedRequest => todo!(),
-                ClientErrorCode::UnprocessableContent => todo!(),
-                ClientErrorCode::Locked => todo!(),
-                ClientErrorCode::FailedDependency => todo!(),
-                ClientErrorCode::TooEarly => todo!(),
-                ClientErrorCode::UpgradeRequired => todo!(),
-                ClientErrorCode::PreconditionRequired => todo!(),
-                ClientErrorCode::TooManyRequests => todo!(),
-                ClientErrorCode::RequestHeaderFieldsTooLarge => todo!(),
-                ClientErrorCode::UnavailableForLegalReasons => todo!(),
+                ClientErrorCode::BadRequest => "Bad Request",
+                ClientErrorCode::Unauthorized => "Unauthorized",
+                ClientErrorCode::PaymentRequired => "Payment Required",
+                ClientErrorCode::Forbidden => "Forbidden",
+                ClientErrorCode::NotFound => "Not Found",
+                ClientErrorCode::MethodNotAllowed => "Method Not Allowed",
+                ClientErrorCode::NotAcceptable => "Not Acceptable",
+                ClientErrorCode::ProxyAuthenticationRequired => "Proxy Authentication Required",
+                ClientErrorCode::RequestTimeout => "Request Timeout",
+                ClientErrorCode::Conflict => "Conflict",
+                ClientErrorCode::Gone => "Gone",
+                ClientErrorCode::LengthRequired => "Length Required",
+                ClientErrorCode::PreconditionFailed => "Precondition Failed",
+                ClientErrorCode::ContentTooLarge => "Content Too Large",
+                ClientErrorCode::URITooLong => "URI Too Long",
+                ClientErrorCode::UnsupportedMediaType => "Unsupported Media Type",
+                ClientErrorCode::RangeNotSatisfiable => "Range Not Satisfiable",
+                ClientErrorCode::ExpectationFailed => "Expectation Failed",
+                ClientErrorCode::ImATeapot => "I'm a teapot",
+                ClientErrorCode::MisdirectedRequest => "Misdirected Request",
+                ClientErrorCode::UnprocessableContent => "Unprocessable Content",
+                ClientErrorCode::Locked => "Locked",
+                ClientErrorCode::FailedDependency => "Failed Dependency",
+                ClientErrorCode::TooEarly => "Too Early",
+                ClientErrorCode::UpgradeRequired => "Upgrade Required",
+                ClientErrorCode::PreconditionRequired => "Precondition Required",
+                ClientErrorCode::TooManyRequests => "Too Many Requests",
+                ClientErrorCode::RequestHeaderFieldsTooLarge => "Request Header Fields Too Large",
+                ClientErrorCode::UnavailableForLegalReasons => "Unavailable For Legal Reasons",
             },
             HTTPStatusCode::ServerError(code) => match code {
-                ServerErrorCode::InternalServerError => write!(f, "Internal Server Error"),
-                ServerErrorCode::NotImplemented => todo!(),
-                ServerErrorCode::BadGateway => todo!(),
-                ServerErrorCode::ServiceUnavailable => todo!(),
-                ServerErrorCode::GatewayTimeout => todo!(),
-                ServerErrorCode::HTTPVersionNotSupported => todo!(),
-                ServerErrorCode::VariantAlsoNegotiates => todo!(),
-                ServerErrorCode::InsufficientStorage => todo!(),
-                ServerErrorCode::LoopDetected => todo!(),
-                ServerErrorCode::NotExtended => todo!(),
-                ServerErrorCode::NetworkAuthenticationRequired => todo!(),
+                ServerErrorCode::InternalServerError => "Internal Server Error",
+                ServerErrorCode::NotImplemented => "Not Implemented",
+                ServerErrorCode::BadGateway => "Bad Gateway",
+                ServerErrorCode::ServiceUnavailable => "Service Unavailable",
+                ServerErrorCode::GatewayTimeout => "Gateway Timeout",
+                ServerErrorCode::HTTPVersionNotSupported => "HTTP Version Not Supported",
+                ServerErrorCode::VariantAlsoNegotiates => "Variant Also Negotiates",
+                ServerErrorCode::InsufficientStorage => "Insufficient Storage",
+                ServerErrorCode::LoopDetected => "Loop Detected",
+                ServerErrorCode::NotExtended => "Not Extended",
+                ServerErrorCode::NetworkAuthenticationRequired => "Network Authentication Required",
             },
         }
     }
+
+    pub fn is_informal(&self) -> bool {
+        matches!(self, HTTPStatusCode::Informal(_))
+    }
+
+    pub fn is_success(&self) -> bool {
+        matches!(self, HTTPStatusCode::Success(_))
+    }
+
+    pub fn is_redirection(&self) -> bool {
+        matches!(self, HTTPStatusCode::Redirection(_))
+    }
+
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self,
+            HTTPStatusCode::ClientError(_) | HTTPStatusCode::ServerError(_)
+        )
+    }
+}
+
+impl TryFrom<u16> for HTTPStatusCode {
+    type Error = u16;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        let code = match value {
+            100 => HTTPStatusCode::Informal(InformalCode::Continue),
+            101 => HTTPStatusCode::Informal(InformalCode::SwitchingProtocols),
+            102 => HTTPStatusCode::Informal(InformalCode::Processing),
+            103 => HTTPStatusCode::Informal(InformalCode::EarlyHints),
+            200 => HTTPStatusCode::Success(SuccessCode::OK),
+            201 => HTTPStatusCode::Success(SuccessCode::Created),
+            202 => HTTPStatusCode::Success(SuccessCode::Accepted),
+            203 => HTTPStatusCode::Success(SuccessCode::NonAuthorativeInformation),
+            204 => HTTPStatusCode::Success(SuccessCode::NoContent),
+            205 => HTTPStatusCode::Success(SuccessCode::ResetContent),
+            206 => HTTPStatusCode::Success(SuccessCode::PartialContent),
+            207 => HTTPStatusCode::Success(SuccessCode::MultiStatus),
+            208 => HTTPStatusCode::Success(SuccessCode::AlreadyReported),
+            226 => HTTPStatusCode::Success(SuccessCode::IMUsed),
+            300 => HTTPStatusCode::Redirection(RedirectionCode::MultipleChoices),
+            301 => HTTPStatusCode::Redirection(RedirectionCode::MovedPermanently),
+            302 => HTTPStatusCode::Redirection(RedirectionCode::Found),
+            303 => HTTPStatusCode::Redirection(RedirectionCode::SeeOther),
+            304 => HTTPStatusCode::Redirection(RedirectionCode::NotModified),
+            305 => HTTPStatusCode::Redirection(RedirectionCode::UseProxy),
+            307 => HTTPStatusCode::Redirection(RedirectionCode::TemporaryRedirect),
+            308 => HTTPStatusCode::Redirection(RedirectionCode::PermanentRedirect),
+            400 => HTTPStatusCode::ClientError(ClientErrorCode::BadRequest),
+            401 => HTTPStatusCode::ClientError(ClientErrorCode::Unauthorized),
+            402 => HTTPStatusCode::ClientError(ClientErrorCode::PaymentRequired),
+            403 => HTTPStatusCode::ClientError(ClientErrorCode::Forbidden),
+            404 => HTTPStatusCode::ClientError(ClientErrorCode::NotFound),
+            405 => HTTPStatusCode::ClientError(ClientErrorCode::MethodNotAllowed),
+            406 => HTTPStatusCode::ClientError(ClientErrorCode::NotAcceptable),
+            407 => HTTPStatusCode::ClientError(ClientErrorCode::ProxyAuthenticationRequired),
+            408 => HTTPStatusCode::ClientError(ClientErrorCode::RequestTimeout),
+            409 => HTTPStatusCode::ClientError(ClientErrorCode::Conflict),
+            410 => HTTPStatusCode::ClientError(ClientErrorCode::Gone),
+            411 => HTTPStatusCode::ClientError(ClientErrorCode::LengthRequired),
+            412 => HTTPStatusCode::ClientError(ClientErrorCode::PreconditionFailed),
+            413 => HTTPStatusCode::ClientError(ClientErrorCode::ContentTooLarge),
+            414 => HTTPStatusCode::ClientError(ClientErrorCode::URITooLong),
+            415 => HTTPStatusCode::ClientError(ClientErrorCode::UnsupportedMediaType),
+            416 => HTTPStatusCode::ClientError(ClientErrorCode::RangeNotSatisfiable),
+            417 => HTTPStatusCode::ClientError(ClientErrorCode::ExpectationFailed),
+            418 => HTTPStatusCode::ClientError(ClientErrorCode::ImATeapot),
+            421 => HTTPStatusCode::ClientError(ClientErrorCode::MisdirectedRequest),
+            422 => HTTPStatusCode::ClientError(ClientErrorCode::UnprocessableContent),
+            423 => HTTPStatusCode::ClientError(ClientErrorCode::Locked),
+            424 => HTTPStatusCode::ClientError(ClientErrorCode::FailedDependency),
+            425 => HTTPStatusCode::ClientError(ClientErrorCode::TooEarly),
+            426 => HTTPStatusCode::ClientError(ClientErrorCode::UpgradeRequired),
+            428 => HTTPStatusCode::ClientError(ClientErrorCode::PreconditionRequired),
+            429 => HTTPStatusCode::ClientError(ClientErrorCode::TooManyRequests),
+            431 => HTTPStatusCode::ClientError(ClientErrorCode::RequestHeaderFieldsTooLarge),
+            451 => HTTPStatusCode::ClientError(ClientErrorCode::UnavailableForLegalReasons),
+            500 => HTTPStatusCode::ServerError(ServerErrorCode::InternalServerError),
+            501 => HTTPStatusCode::ServerError(ServerErrorCode::NotImplemented),
+            502 => HTTPStatusCode::ServerError(ServerErrorCode::BadGateway),
+            503 => HTTPStatusCode::ServerError(ServerErrorCode::ServiceUnavailable),
+            504 => HTTPStatusCode::ServerError(ServerErrorCode::GatewayTimeout),
+            505 => HTTPStatusCode::ServerError(ServerErrorCode::HTTPVersionNotSupported),
+            506 => HTTPStatusCode::ServerError(ServerErrorCode::VariantAlsoNegotiates),
+            507 => HTTPStatusCode::ServerError(ServerErrorCode::InsufficientStorage),
+            508 => HTTPStatusCode::ServerError(ServerErrorCode::LoopDetected),
+            510 => HTTPStatusCode::ServerError(ServerErrorCode::NotExtended),
+            511 => HTTPStatusCode::ServerError(ServerErrorCode::NetworkAuthenticationRequired),
+            v => return Err(v),
+        };
+
+        Ok(code)
+    }
+}
+
+impl From<HTTPStatusCode> for u16 {
+    fn from(value: HTTPStatusCode) -> Self {
+        value.to_value()
+    }
+}
+
+impl fmt::Display for HTTPStatusCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.reason())
+    }
 }
@@ -0,0 +1,104 @@
+use std::{
+    io::{self, Read, Write},
+    net::{Shutdown, TcpStream},
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use rustls::{ServerConnection, StreamOwned};
+
+type TlsStream = StreamOwned<ServerConnection, TcpStream>;
+
+// -> An accepted connection, plaintext or TLS, with handles that share it like TcpStream::try_clone
+pub enum Stream {
+    Plain(TcpStream),
+    // -> Reads and writes of a connection never overlap, so the lock is never contended
+    Tls(Arc<Mutex<TlsStream>>),
+}
+
+impl Stream {
+    // -> The handshake runs on the first read or write, on the worker rather than the accept loop
+    pub fn tls(connection: ServerConnection, socket: TcpStream) -> Stream {
+        Stream::Tls(Arc::new(Mutex::new(StreamOwned::new(connection, socket))))
+    }
+
+    pub fn is_secure(&self) -> bool {
+        matches!(self, Stream::Tls(_))
+    }
+
+    pub fn try_clone(&self) -> io::Result<Stream> {
+        match self {
+            Stream::Plain(s) => Ok(Stream::Plain(s.try_clone()?)),
+            Stream::Tls(s) => Ok(Stream::Tls(Arc::clone(s))),
+        }
+    }
+
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            Stream::Plain(s) => s.set_read_timeout(timeout),
+            Stream::Tls(s) => lock(s)?.sock.set_read_timeout(timeout),
+        }
+    }
+
+    // -> Finishes the TLS handshake to learn the protocol ALPN settled on
+    pub fn alpn_protocol(&self) -> Option<Vec<u8>> {
+        let mut stream = match self {
+            Stream::Plain(_) => return None,
+            Stream::Tls(s) => lock(s).ok()?,
+        };
+
+        let TlsStream { conn, sock } = &mut *stream;
+        while conn.is_handshaking() {
+            if conn.complete_io(sock).is_err() {
+                return None;
+            }
+        }
+
+        conn.alpn_protocol().map(|p| p.to_vec())
+    }
+
+    // -> Sends close_notify first, so TLS clients can tell the end from a truncation
+    pub fn close(&self) {
+        match self {
+            Stream::Plain(_) => (),
+            Stream::Tls(s) => {
+                if let Ok(mut stream) = lock(s) {
+                    stream.conn.send_close_notify();
+                    let _ = stream.flush();
+                    let _ = stream.sock.shutdown(Shutdown::Write);
+                }
+            }
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Plain(s) => s.read(buf),
+            Stream::Tls(s) => lock(s)?.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Plain(s) => s.write(buf),
+            Stream::Tls(s) => lock(s)?.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Plain(s) => s.flush(),
+            Stream::Tls(s) => lock(s)?.flush(),
+        }
+    }
+}
+
+fn lock(stream: &Mutex<TlsStream>) -> io::Result<MutexGuard<'_, TlsStream>> {
+    stream
+        .lock()
+        .map_err(|_| io::Error::other("TLS stream lock poisoned"))
+}
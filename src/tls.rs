@@ -0,0 +1,342 @@
+use std::{
+    fmt, fs,
+    net::TcpStream,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, RwLock},
+    time::SystemTime,
+};
+
+use rustls::{
+    ServerConfig as RustlsConfig, ServerConnection, SupportedProtocolVersion,
+    crypto::{CryptoProvider, ring},
+    pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    HTTPRequest, HTTPResponse, LogLevel,
+    config::ServerConfig,
+    handler::Handler,
+    log,
+    status::{ClientErrorCode, HTTPStatusCode, RedirectionCode},
+    stream::Stream,
+    vhost::{self, HostPattern},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum TlsVersion {
+    #[serde(rename = "1.2")]
+    Tls12,
+    #[serde(rename = "1.3")]
+    Tls13,
+}
+
+impl TlsVersion {
+    fn protocol(&self) -> &'static SupportedProtocolVersion {
+        match self {
+            TlsVersion::Tls12 => &rustls::version::TLS12,
+            TlsVersion::Tls13 => &rustls::version::TLS13,
+        }
+    }
+}
+
+impl FromStr for TlsVersion {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<TlsVersion, String> {
+        let lower = input.trim().to_ascii_lowercase();
+        let version = lower
+            .strip_prefix("tlsv")
+            .or_else(|| lower.strip_prefix("tls"))
+            .unwrap_or(&lower);
+
+        match version {
+            "1.2" => Ok(TlsVersion::Tls12),
+            "1.3" => Ok(TlsVersion::Tls13),
+            _ => Err(String::from(input)),
+        }
+    }
+}
+
+impl fmt::Display for TlsVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TlsVersion::Tls12 => write!(f, "1.2"),
+            TlsVersion::Tls13 => write!(f, "1.3"),
+        }
+    }
+}
+
+// -> One certificate and the host names it is picked for, empty for the fallback
+struct Certificate {
+    patterns: Vec<HostPattern>,
+    cert: PathBuf,
+    key: PathBuf,
+    modified: (Option<SystemTime>, Option<SystemTime>),
+    loaded: Arc<CertifiedKey>,
+}
+
+// -> Picks a certificate by SNI, reloading swaps keys for new handshakes only
+#[derive(Default)]
+pub struct Certificates {
+    entries: RwLock<Vec<Certificate>>,
+}
+
+impl Certificates {
+    pub fn new() -> Certificates {
+        Certificates::default()
+    }
+
+    // -> Vhost certificates by their names, the top-level one (or the default vhost's) for the rest
+    pub fn from_config(config: &ServerConfig) -> Result<Certificates, String> {
+        let mut certificates = Certificates::new();
+
+        for vhost in &config.vhosts {
+            if let (Some(cert), Some(key)) = (&vhost.cert, &vhost.key) {
+                let patterns = vhost
+                    .names
+                    .iter()
+                    .map(|n| HostPattern::parse(n))
+                    .collect::<Result<_, _>>()?;
+                certificates = certificates.add(patterns, cert, key)?;
+            }
+        }
+
+        let fallback = match (&config.tls.cert, &config.tls.key) {
+            (Some(cert), Some(key)) => Some((cert, key)),
+            _ => config
+                .vhosts
+                .iter()
+                .find(|v| v.default)
+                .and_then(|v| v.cert.as_ref().zip(v.key.as_ref())),
+        };
+
+        if let Some((cert, key)) = fallback {
+            certificates = certificates.add(Vec::new(), cert, key)?;
+        }
+
+        Ok(certificates)
+    }
+
+    pub fn add(
+        self,
+        patterns: Vec<HostPattern>,
+        cert: &Path,
+        key: &Path,
+    ) -> Result<Certificates, String> {
+        let loaded = load(cert, key)?;
+
+        if let Ok(mut entries) = self.entries.write() {
+            entries.push(Certificate {
+                patterns,
+                cert: cert.to_path_buf(),
+                key: key.to_path_buf(),
+                modified: (modified(cert), modified(key)),
+                loaded: Arc::new(loaded),
+            });
+        }
+
+        Ok(self)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self.entries.read() {
+            Ok(entries) => entries.is_empty(),
+            Err(_) => true,
+        }
+    }
+
+    // -> Reloads every pair whose files changed, a broken pair keeps serving the old one
+    pub fn reload(&self) {
+        let mut entries = match self.entries.write() {
+            Ok(e) => e,
+            Err(_) => return,
+        };
+
+        for entry in entries.iter_mut() {
+            let current = (modified(&entry.cert), modified(&entry.key));
+            if current == entry.modified {
+                continue;
+            }
+
+            match load(&entry.cert, &entry.key) {
+                Ok(loaded) => {
+                    entry.loaded = Arc::new(loaded);
+                    entry.modified = current;
+                    log(
+                        LogLevel::Info,
+                        format!("Reloaded certificate {}", entry.cert.display()),
+                    );
+                }
+                Err(e) => log(LogLevel::Error, e),
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Certificates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let paths: Vec<PathBuf> = match self.entries.read() {
+            Ok(entries) => entries.iter().map(|e| e.cert.clone()).collect(),
+            Err(_) => Vec::new(),
+        };
+        f.debug_struct("Certificates")
+            .field("certs", &paths)
+            .finish()
+    }
+}
+
+impl ResolvesServerCert for Certificates {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let entries = self.entries.read().ok()?;
+        let name = client_hello.server_name().map(vhost::normalize_host);
+
+        let matched = name.and_then(|name| {
+            entries
+                .iter()
+                .flat_map(|e| e.patterns.iter().map(move |p| (p, e)))
+                .filter(|(p, _)| p.matches(&name))
+                .min_by_key(|(p, _)| p.rank())
+                .map(|(_, e)| e)
+        });
+
+        // -> No SNI or an unknown name gets the fallback, without one the handshake fails
+        matched
+            .or_else(|| entries.iter().find(|e| e.patterns.is_empty()))
+            .map(|e| Arc::clone(&e.loaded))
+    }
+}
+
+// -> Wraps accepted sockets of the HTTPS listeners
+pub struct TlsAcceptor {
+    config: Arc<RustlsConfig>,
+    certificates: Arc<Certificates>,
+}
+
+impl TlsAcceptor {
+    pub fn new(
+        certificates: Certificates,
+        versions: &[TlsVersion],
+        protocols: &[&str],
+    ) -> Result<TlsAcceptor, String> {
+        let certificates = Arc::new(certificates);
+        let versions: Vec<&'static SupportedProtocolVersion> =
+            versions.iter().map(TlsVersion::protocol).collect();
+
+        let provider: Arc<CryptoProvider> = Arc::new(ring::default_provider());
+        let builder =
+            match RustlsConfig::builder_with_provider(provider).with_protocol_versions(&versions) {
+                Ok(b) => b,
+                Err(e) => return Err(format!("Unable to set up TLS: {e}")),
+            };
+
+        let mut config = builder
+            .with_no_client_auth()
+            .with_cert_resolver(Arc::clone(&certificates) as Arc<dyn ResolvesServerCert>);
+        config.alpn_protocols = protocols.iter().map(|p| p.as_bytes().to_vec()).collect();
+
+        Ok(TlsAcceptor {
+            config: Arc::new(config),
+            certificates,
+        })
+    }
+
+    pub fn from_config(config: &ServerConfig) -> Result<TlsAcceptor, String> {
+        let certificates = Certificates::from_config(config)?;
+
+        if certificates.is_empty() {
+            return Err(String::from(
+                "HTTPS listeners need at least one certificate",
+            ));
+        }
+
+        // -> ALPN lists the preferred protocol first
+        let protocols: &[&str] = match config.http2 {
+            true => &["h2", "http/1.1"],
+            false => &["http/1.1"],
+        };
+
+        TlsAcceptor::new(certificates, &config.tls.versions, protocols)
+    }
+
+    pub fn accept(&self, socket: TcpStream) -> Result<Stream, String> {
+        match ServerConnection::new(Arc::clone(&self.config)) {
+            Ok(connection) => Ok(Stream::tls(connection, socket)),
+            Err(e) => Err(format!("Unable to start TLS session: {e}")),
+        }
+    }
+
+    pub fn certificates(&self) -> &Certificates {
+        &self.certificates
+    }
+}
+
+// -> Answers every request on a plaintext listener with the same URL on HTTPS
+pub struct HttpsRedirect {
+    port: u16,
+}
+
+impl HttpsRedirect {
+    pub fn new(port: u16) -> HttpsRedirect {
+        HttpsRedirect { port }
+    }
+}
+
+impl Handler for HttpsRedirect {
+    fn handle(&self, request: &mut HTTPRequest) -> HTTPResponse {
+        let host = match request.host() {
+            Some(h) if !h.is_empty() => h,
+            _ => {
+                return HTTPResponse::builder()
+                    .status(HTTPStatusCode::ClientError(ClientErrorCode::BadRequest))
+                    .build();
+            }
+        };
+
+        let authority = match self.port {
+            443 => host,
+            port => format!("{host}:{port}"),
+        };
+
+        // -> 308 keeps the method and body, unlike 301
+        HTTPResponse::redirect(
+            RedirectionCode::PermanentRedirect,
+            format!("https://{authority}{}", request.path.to_string_lossy()),
+        )
+    }
+}
+
+fn load(cert: &Path, key: &Path) -> Result<CertifiedKey, String> {
+    let chain: Vec<CertificateDer<'static>> = match CertificateDer::pem_file_iter(cert) {
+        Ok(iter) => match iter.collect() {
+            Ok(chain) => chain,
+            Err(e) => return Err(format!("Unable to read {}: {e}", cert.display())),
+        },
+        Err(e) => return Err(format!("Unable to read {}: {e}", cert.display())),
+    };
+
+    if chain.is_empty() {
+        return Err(format!("{} has no certificate", cert.display()));
+    }
+
+    let private_key = match PrivateKeyDer::from_pem_file(key) {
+        Ok(k) => k,
+        Err(e) => return Err(format!("Unable to read {}: {e}", key.display())),
+    };
+
+    match CertifiedKey::from_der(chain, private_key, &ring::default_provider()) {
+        Ok(loaded) => Ok(loaded),
+        Err(e) => Err(format!(
+            "{} doesn't fit {}: {e}",
+            key.display(),
+            cert.display()
+        )),
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
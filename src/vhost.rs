@@ -0,0 +1,406 @@
+use std::sync::Arc;
+
+use crate::{
+    HTTPMethod, HTTPRequest, HTTPResponse,
+    config::{ServerConfig, VirtualHostConfig},
+    files::Files,
+    handler::{Handler, Stack, merge_methods},
+    middleware::DefaultHeaders,
+    resolve::Resolver,
+    router::Router,
+    status::{ClientErrorCode, HTTPStatusCode},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostPattern {
+    // -> "*", any host
+    Any,
+    // -> "example.com"
+    Exact(String),
+    // -> "*.example.com", stored as ".example.com", matches any subdomain
+    Suffix(String),
+}
+
+impl HostPattern {
+    pub fn parse(pattern: &str) -> Result<HostPattern, String> {
+        // -> "*." loses its trailing dot below and would pass for "*"
+        let bare_suffix = pattern.trim().starts_with("*.");
+        let pattern = normalize_host(pattern);
+
+        if pattern == "*" && !bare_suffix {
+            return Ok(HostPattern::Any);
+        }
+
+        let (suffix, name) = match pattern.strip_prefix("*.") {
+            Some(rest) => (true, rest),
+            None => (false, pattern.as_str()),
+        };
+
+        if name.is_empty() || name.contains('*') || !name.bytes().all(is_host_char) {
+            return Err(format!("\"{pattern}\" is not a valid host pattern"));
+        }
+
+        match suffix {
+            true => Ok(HostPattern::Suffix(format!(".{name}"))),
+            false => Ok(HostPattern::Exact(String::from(name))),
+        }
+    }
+
+    pub fn matches(&self, host: &str) -> bool {
+        match self {
+            HostPattern::Any => true,
+            HostPattern::Exact(name) => name == host,
+            HostPattern::Suffix(suffix) => host.len() > suffix.len() && host.ends_with(suffix),
+        }
+    }
+
+    // -> Exact names first, then the longest suffix, "*" last
+    pub fn rank(&self) -> (u8, usize) {
+        match self {
+            HostPattern::Exact(_) => (0, 0),
+            HostPattern::Suffix(s) => (1, usize::MAX - s.len()),
+            HostPattern::Any => (2, 0),
+        }
+    }
+}
+
+struct VirtualHost {
+    patterns: Vec<HostPattern>,
+    handler: Arc<dyn Handler>,
+}
+
+// -> Picks a handler by the Host header, unmatched hosts go to the default
+pub struct VirtualHosts {
+    hosts: Vec<VirtualHost>,
+    default: Option<Arc<dyn Handler>>,
+}
+
+impl VirtualHosts {
+    pub fn new() -> VirtualHosts {
+        VirtualHosts {
+            hosts: Vec::new(),
+            default: None,
+        }
+    }
+
+    pub fn from_config(config: &ServerConfig) -> VirtualHosts {
+        let mut hosts = VirtualHosts::new();
+        let mut default: Option<Arc<dyn Handler>> = None;
+
+        for vhost in &config.vhosts {
+            let handler: Arc<dyn Handler> = Arc::new(site(config, vhost));
+
+            // -> ServerConfig::validate() already refused invalid names, nothing is dropped here
+            let patterns = vhost
+                .names
+                .iter()
+                .filter_map(|n| HostPattern::parse(n).ok())
+                .collect();
+
+            if vhost.default && default.is_none() {
+                default = Some(Arc::clone(&handler));
+            }
+
+            hosts.hosts.push(VirtualHost { patterns, handler });
+        }
+
+        // -> Without a default vhost the top-level root answers unknown hosts
+        hosts.default = Some(default.unwrap_or_else(|| Arc::new(Router::new())));
+        hosts
+    }
+
+    // -> Panics on an invalid pattern, like a route with a bad pattern would be a programming error
+    pub fn host<H: Handler + 'static>(mut self, names: &[&str], handler: H) -> VirtualHosts {
+        let patterns = names
+            .iter()
+            .map(|n| match HostPattern::parse(n) {
+                Ok(p) => p,
+                Err(e) => panic!("{e}"),
+            })
+            .collect();
+
+        self.hosts.push(VirtualHost {
+            patterns,
+            handler: Arc::new(handler),
+        });
+        self
+    }
+
+    pub fn default<H: Handler + 'static>(mut self, handler: H) -> VirtualHosts {
+        self.default = Some(Arc::new(handler));
+        self
+    }
+
+    fn select(&self, host: Option<&str>) -> Option<&Arc<dyn Handler>> {
+        let mut best: Option<(&VirtualHost, (u8, usize))> = None;
+
+        if let Some(host) = host {
+            for vhost in &self.hosts {
+                for pattern in vhost.patterns.iter().filter(|p| p.matches(host)) {
+                    let rank = pattern.rank();
+
+                    // -> Ties go to the host registered first
+                    if best.is_none_or(|(_, current)| rank < current) {
+                        best = Some((vhost, rank));
+                    }
+                }
+            }
+        }
+
+        match best {
+            Some((vhost, _)) => Some(&vhost.handler),
+            None => self.default.as_ref(),
+        }
+    }
+}
+
+impl Default for VirtualHosts {
+    fn default() -> Self {
+        VirtualHosts::new()
+    }
+}
+
+impl Handler for VirtualHosts {
+    fn handle(&self, request: &mut HTTPRequest) -> HTTPResponse {
+        let host = request.host();
+
+        match self.select(host.as_deref()) {
+            Some(handler) => handler.handle(request),
+            None => HTTPResponse::builder()
+                .status(HTTPStatusCode::ClientError(
+                    ClientErrorCode::MisdirectedRequest,
+                ))
+                .build(),
+        }
+    }
+
+    fn methods(&self) -> Vec<HTTPMethod> {
+        let mut methods: Vec<HTTPMethod> = Vec::new();
+        for handler in self.hosts.iter().map(|h| &h.handler).chain(&self.default) {
+            merge_methods(&mut methods, handler.methods());
+        }
+        methods
+    }
+}
+
+// -> Lowercase, without the port and without a trailing dot
+pub fn normalize_host(host: &str) -> String {
+    let host = host.trim();
+
+    let name = match host.strip_prefix('[') {
+        // -> IPv6 literal, "[::1]:8080"
+        Some(rest) => match rest.split_once(']') {
+            Some((ip, _)) => &host[..ip.len() + 2],
+            None => host,
+        },
+        None => match host.rsplit_once(':') {
+            Some((name, port)) if port.bytes().all(|b| b.is_ascii_digit()) => name,
+            _ => host,
+        },
+    };
+
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
+
+pub fn is_valid_host(host: &str) -> bool {
+    host.bytes()
+        .all(|b| is_host_char(b) || b"[]:%".contains(&b))
+}
+
+fn is_host_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"-._~!$&'()*+,;=".contains(&b)
+}
+
+fn site(config: &ServerConfig, vhost: &VirtualHostConfig) -> Stack {
+    let resolver = Resolver::new(&vhost.root)
+        .symlinks(config.symlinks)
+        .serve_hidden(config.serve_hidden);
+
+    let index_files = match &vhost.index_files {
+        Some(files) => files.clone(),
+        None => config.index_files.clone(),
+    };
+
+    let files = Files::new(resolver)
+        .index_files(index_files)
+        .autoindex(vhost.autoindex.unwrap_or(config.autoindex))
+        .etag(config.etag)
+        .precompressed(config.precompressed);
+
+    let router = Router::new().fallback(files);
+
+    let headers = vhost
+        .headers
+        .iter()
+        .fold(DefaultHeaders::new(), |h, (name, value)| {
+            h.header(name, value)
+        });
+
+    Stack::new(router).wrap(headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    use crate::parser::parse_request;
+
+    fn site(name: &'static str) -> impl Handler {
+        move |_: &mut HTTPRequest| HTTPResponse::builder().header("X-Site", name).build()
+    }
+
+    fn served(hosts: &VirtualHosts, host: Option<&str>) -> Option<String> {
+        let input = match host {
+            Some(host) => format!("GET / HTTP/1.1\r\nHost: {host}\r\n\r\n"),
+            None => String::from("GET / HTTP/1.0\r\n\r\n"),
+        };
+        let mut request = parse_request(&mut Cursor::new(input.into_bytes())).unwrap();
+
+        let response = hosts.handle(&mut request);
+        response.headers.get("X-Site").map(String::from)
+    }
+
+    #[test]
+    fn parses_host_patterns() {
+        assert_eq!(HostPattern::parse("*"), Ok(HostPattern::Any));
+        assert_eq!(
+            HostPattern::parse("Example.COM."),
+            Ok(HostPattern::Exact(String::from("example.com")))
+        );
+        assert_eq!(
+            HostPattern::parse("*.example.com"),
+            Ok(HostPattern::Suffix(String::from(".example.com")))
+        );
+        assert_eq!(
+            HostPattern::parse("example.com:8080"),
+            Ok(HostPattern::Exact(String::from("example.com")))
+        );
+    }
+
+    #[test]
+    fn refuses_invalid_host_patterns() {
+        for pattern in [
+            "",
+            "*.",
+            "www.*.com",
+            "www*.example.com",
+            "*example.com",
+            "a b.com",
+            "a/b",
+        ] {
+            assert!(HostPattern::parse(pattern).is_err(), "{pattern:?}");
+        }
+    }
+
+    #[test]
+    fn suffix_matches_subdomains_only() {
+        let pattern = HostPattern::parse("*.example.com").unwrap();
+
+        assert!(pattern.matches("www.example.com"));
+        assert!(pattern.matches("a.b.example.com"));
+        assert!(!pattern.matches("example.com"));
+        assert!(!pattern.matches(".example.com"));
+        assert!(!pattern.matches("badexample.com"));
+    }
+
+    #[test]
+    fn normalizes_hosts() {
+        assert_eq!(normalize_host("Example.COM"), "example.com");
+        assert_eq!(normalize_host("example.com:8080"), "example.com");
+        assert_eq!(normalize_host("example.com.:443"), "example.com");
+        assert_eq!(normalize_host(" example.com. "), "example.com");
+        assert_eq!(normalize_host("[::1]:8080"), "[::1]");
+        assert_eq!(normalize_host("[2001:DB8::1]"), "[2001:db8::1]");
+        assert_eq!(normalize_host("127.0.0.1:7878"), "127.0.0.1");
+        // -> Not a port, kept as it is
+        assert_eq!(normalize_host("example.com:http"), "example.com:http");
+    }
+
+    #[test]
+    fn exact_beats_suffix_and_any() {
+        let hosts = VirtualHosts::new()
+            .host(&["*"], site("any"))
+            .host(&["*.example.com"], site("suffix"))
+            .host(&["www.example.com"], site("exact"));
+
+        assert_eq!(
+            served(&hosts, Some("www.example.com")).as_deref(),
+            Some("exact")
+        );
+        assert_eq!(
+            served(&hosts, Some("shop.example.com")).as_deref(),
+            Some("suffix")
+        );
+        assert_eq!(served(&hosts, Some("other.test")).as_deref(), Some("any"));
+    }
+
+    #[test]
+    fn longer_suffix_wins() {
+        let hosts = VirtualHosts::new()
+            .host(&["*.example.com"], site("short"))
+            .host(&["*.api.example.com"], site("long"));
+
+        assert_eq!(
+            served(&hosts, Some("v1.api.example.com")).as_deref(),
+            Some("long")
+        );
+        assert_eq!(
+            served(&hosts, Some("api.example.com")).as_deref(),
+            Some("short")
+        );
+    }
+
+    #[test]
+    fn ties_go_to_the_host_registered_first() {
+        let hosts = VirtualHosts::new()
+            .host(&["www.example.com"], site("first"))
+            .host(&["www.example.com", "*.example.com"], site("second"));
+
+        assert_eq!(
+            served(&hosts, Some("www.example.com")).as_deref(),
+            Some("first")
+        );
+    }
+
+    #[test]
+    fn matches_the_normalized_host_header() {
+        let hosts = VirtualHosts::new().host(&["www.example.com"], site("exact"));
+
+        assert_eq!(
+            served(&hosts, Some("WWW.Example.com.:8080")).as_deref(),
+            Some("exact")
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_default_host() {
+        let hosts = VirtualHosts::new()
+            .host(&["www.example.com"], site("exact"))
+            .default(site("default"));
+
+        assert_eq!(
+            served(&hosts, Some("other.test")).as_deref(),
+            Some("default")
+        );
+        assert_eq!(served(&hosts, None).as_deref(), Some("default"));
+    }
+
+    #[test]
+    fn misdirects_unknown_hosts_without_a_default() {
+        let hosts = VirtualHosts::new().host(&["www.example.com"], site("exact"));
+
+        let mut request = parse_request(&mut Cursor::new(
+            b"GET / HTTP/1.1\r\nHost: other.test\r\n\r\n".to_vec(),
+        ))
+        .unwrap();
+        assert_eq!(hosts.handle(&mut request).status.to_value(), 421);
+    }
+
+    #[test]
+    #[should_panic]
+    fn panics_on_an_invalid_pattern() {
+        let _ = VirtualHosts::new().host(&["www.*.com"], site("bad"));
+    }
+}
@@ -0,0 +1,22 @@
+use std::{env, fs, path::PathBuf, process};
+
+// -> A scratch directory under the system temp dir, removed again when dropped
+pub struct Sandbox {
+    pub base: PathBuf,
+}
+
+impl Sandbox {
+    // -> The name keeps tests running at the same time apart, the process id separate runs
+    pub fn new(name: &str) -> Sandbox {
+        let base = env::temp_dir().join(format!("rws-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&base);
+        fs::create_dir_all(&base).unwrap();
+        Sandbox { base }
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.base);
+    }
+}
@@ -0,0 +1,227 @@
+mod common;
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use rust_web_server::{
+    RequestURL,
+    resolve::{Resolver, SymlinkPolicy},
+    status::{ClientErrorCode, HTTPStatusCode},
+};
+
+use common::Sandbox;
+
+// -> base/
+//      root/index.html, root/.secret, root/sub/page.html, root/inner -> root/sub
+//      root/escape -> base/outside, outside/passwd
+fn sandbox(name: &str) -> Sandbox {
+    let sandbox = Sandbox::new(name);
+    let base = &sandbox.base;
+
+    fs::create_dir_all(base.join("root/sub")).unwrap();
+    fs::create_dir_all(base.join("outside")).unwrap();
+    fs::write(base.join("root/index.html"), "index").unwrap();
+    fs::write(base.join("root/.secret"), "secret").unwrap();
+    fs::write(base.join("root/sub/page.html"), "page").unwrap();
+    fs::write(base.join("outside/passwd"), "passwd").unwrap();
+
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(base.join("outside"), base.join("root/escape")).unwrap();
+        std::os::unix::fs::symlink(base.join("root/sub"), base.join("root/inner")).unwrap();
+    }
+
+    sandbox
+}
+
+trait Site {
+    fn root(&self) -> PathBuf;
+    fn resolver(&self) -> Resolver;
+}
+
+impl Site for Sandbox {
+    fn root(&self) -> PathBuf {
+        self.base.join("root")
+    }
+
+    fn resolver(&self) -> Resolver {
+        Resolver::new(self.root())
+    }
+}
+
+fn resolve(resolver: &Resolver, target: &str) -> Result<PathBuf, HTTPStatusCode> {
+    RequestURL::normalize(target).and_then(|url| resolver.resolve(&url))
+}
+
+fn bad_request() -> HTTPStatusCode {
+    HTTPStatusCode::ClientError(ClientErrorCode::BadRequest)
+}
+
+fn not_found() -> HTTPStatusCode {
+    HTTPStatusCode::ClientError(ClientErrorCode::NotFound)
+}
+
+fn forbidden() -> HTTPStatusCode {
+    HTTPStatusCode::ClientError(ClientErrorCode::Forbidden)
+}
+
+fn inside(root: &Path, path: &Path) -> bool {
+    path.starts_with(root.canonicalize().unwrap())
+}
+
+#[test]
+fn dot_segments_stay_below_root() {
+    let sandbox = sandbox("dots");
+    let resolver = sandbox.resolver();
+
+    let resolved = resolve(&resolver, "/../../../sub/./page.html").unwrap();
+    assert!(inside(&sandbox.root(), &resolved));
+    assert!(resolved.ends_with("sub/page.html"));
+
+    let resolved = resolve(&resolver, "/%2e%2e/%2e%2e/index.html").unwrap();
+    assert!(resolved.ends_with("root/index.html"));
+
+    assert_eq!(resolve(&resolver, "/../outside/passwd"), Err(not_found()));
+}
+
+#[test]
+fn encoded_separators_and_nul_are_rejected() {
+    let sandbox = sandbox("separators");
+    let resolver = sandbox.resolver();
+
+    for target in [
+        "/sub%2Fpage.html",
+        "/sub%2fpage.html",
+        "/..%2F..%2Foutside%2Fpasswd",
+        "/sub%5Cpage.html",
+        "/sub\\page.html",
+        "/index.html%00.png",
+        "/%FF%FE",
+    ] {
+        assert_eq!(resolve(&resolver, target), Err(bad_request()), "{target}");
+    }
+}
+
+#[test]
+fn query_and_fragment_are_not_part_of_the_path() {
+    let sandbox = sandbox("query");
+    let resolver = sandbox.resolver();
+
+    let resolved = resolve(&resolver, "/index.html?file=../../outside/passwd#top").unwrap();
+    assert!(resolved.ends_with("root/index.html"));
+}
+
+#[test]
+fn dotfiles_are_hidden_by_default() {
+    let sandbox = sandbox("hidden");
+
+    assert_eq!(resolve(&sandbox.resolver(), "/.secret"), Err(not_found()));
+    assert_eq!(resolve(&sandbox.resolver(), "/%2Esecret"), Err(not_found()));
+
+    let resolver = sandbox.resolver().serve_hidden(true);
+    assert!(resolve(&resolver, "/.secret").is_ok());
+}
+
+#[cfg(unix)]
+#[test]
+fn symlink_policies() {
+    let sandbox = sandbox("symlinks");
+
+    let within = sandbox.resolver();
+    assert_eq!(resolve(&within, "/escape/passwd"), Err(forbidden()));
+    assert!(resolve(&within, "/inner/page.html").is_ok());
+
+    let deny = sandbox.resolver().symlinks(SymlinkPolicy::Deny);
+    assert_eq!(resolve(&deny, "/escape/passwd"), Err(forbidden()));
+    assert_eq!(resolve(&deny, "/inner/page.html"), Err(forbidden()));
+    assert!(resolve(&deny, "/sub/page.html").is_ok());
+
+    let follow = sandbox.resolver().symlinks(SymlinkPolicy::Follow);
+    assert!(resolve(&follow, "/escape/passwd").is_ok());
+}
+
+// -> Small xorshift generator so the fuzz run is reproducible without extra crates
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[(self.next() % items.len() as u64) as usize]
+    }
+}
+
+#[test]
+fn fuzzed_targets_never_escape_the_root() {
+    let sandbox = sandbox("fuzz");
+    let root = sandbox.root();
+
+    let fragments = [
+        "/",
+        "//",
+        ".",
+        "..",
+        "%2e",
+        "%2E%2e",
+        "%2f",
+        "%2F",
+        "%5c",
+        "\\",
+        "%00",
+        "%",
+        "%z",
+        "%ff",
+        "sub",
+        "escape",
+        "inner",
+        "outside",
+        "passwd",
+        "index.html",
+        ".secret",
+        "page.html",
+        "?",
+        "#",
+        "+",
+        "%20",
+        "~",
+        "C:",
+        "\u{e9}",
+        "%c3%a9",
+        ";",
+        "*",
+    ];
+
+    let resolvers = [
+        sandbox.resolver(),
+        sandbox.resolver().symlinks(SymlinkPolicy::Deny),
+        sandbox.resolver().serve_hidden(true),
+    ];
+
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+
+    for _ in 0..5000 {
+        let mut target = String::from("/");
+        let length = 1 + rng.next() % 12;
+
+        for _ in 0..length {
+            target.push_str(rng.pick(&fragments));
+        }
+
+        for resolver in &resolvers {
+            if let Ok(path) = resolve(resolver, &target) {
+                assert!(
+                    inside(&root, &path),
+                    "{target} resolved to {}",
+                    path.display()
+                );
+            }
+        }
+    }
+}
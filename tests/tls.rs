@@ -0,0 +1,266 @@
+mod common;
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use common::Sandbox;
+use rust_web_server::{
+    tls::{Certificates, TlsVersion},
+    vhost::HostPattern,
+};
+use rustls::{
+    ClientConfig, ClientConnection, DigitallySignedStruct, ServerConfig, ServerConnection,
+    SignatureScheme,
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::{CryptoProvider, ring},
+    pki_types::{CertificateDer, ServerName, UnixTime},
+    server::ResolvesServerCert,
+};
+
+// -> A self-signed certificate and its key, written next to each other
+struct Pair {
+    cert: PathBuf,
+    key: PathBuf,
+    der: Vec<u8>,
+}
+
+trait Pairs {
+    fn pair(&self, file: &str, names: &[&str]) -> Pair;
+}
+
+impl Pairs for Sandbox {
+    fn pair(&self, file: &str, names: &[&str]) -> Pair {
+        let names: Vec<String> = names.iter().map(|n| String::from(*n)).collect();
+        let generated = rcgen::generate_simple_self_signed(names).unwrap();
+
+        let pair = Pair {
+            cert: self.base.join(format!("{file}.crt")),
+            key: self.base.join(format!("{file}.key")),
+            der: generated.cert.der().to_vec(),
+        };
+
+        fs::write(&pair.cert, generated.cert.pem()).unwrap();
+        fs::write(&pair.key, generated.signing_key.serialize_pem()).unwrap();
+        pair
+    }
+}
+
+fn patterns(names: &[&str]) -> Vec<HostPattern> {
+    names
+        .iter()
+        .map(|n| HostPattern::parse(n).unwrap())
+        .collect()
+}
+
+// -> Copies a file over another and moves its mtime on, so a reload sees the change
+fn replace(from: &Path, to: &Path, age: u64) {
+    fs::copy(from, to).unwrap();
+    let file = fs::File::options().write(true).open(to).unwrap();
+    file.set_modified(SystemTime::now() + Duration::from_secs(age))
+        .unwrap();
+}
+
+// -> The tests only look at which certificate was sent, not whether it is trusted
+#[derive(Debug)]
+struct AcceptAny;
+
+impl ServerCertVerifier for AcceptAny {
+    fn verify_server_cert(
+        &self,
+        _: &CertificateDer<'_>,
+        _: &[CertificateDer<'_>],
+        _: &ServerName<'_>,
+        _: &[u8],
+        _: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        _: &[u8],
+        _: &CertificateDer<'_>,
+        _: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        _: &[u8],
+        _: &CertificateDer<'_>,
+        _: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        ring::default_provider()
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+// -> Runs a handshake in memory and returns the certificate the server picked for `sni`
+fn served(certificates: &Arc<Certificates>, sni: Option<&str>) -> Option<Vec<u8>> {
+    let provider: Arc<CryptoProvider> = Arc::new(ring::default_provider());
+
+    let server_config = ServerConfig::builder_with_provider(Arc::clone(&provider))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_no_client_auth()
+        .with_cert_resolver(Arc::clone(certificates) as Arc<dyn ResolvesServerCert>);
+
+    let mut client_config = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(AcceptAny))
+        .with_no_client_auth();
+    client_config.enable_sni = sni.is_some();
+
+    let name = ServerName::try_from(String::from(sni.unwrap_or("no-sni.test"))).unwrap();
+    let mut client = ClientConnection::new(Arc::new(client_config), name).unwrap();
+    let mut server = ServerConnection::new(Arc::new(server_config)).unwrap();
+
+    for _ in 0..10 {
+        if !client.is_handshaking() {
+            break;
+        }
+
+        let mut buffer = Vec::new();
+        while client.wants_write() {
+            client.write_tls(&mut buffer).unwrap();
+        }
+        server.read_tls(&mut buffer.as_slice()).unwrap();
+        server.process_new_packets().ok()?;
+
+        let mut buffer = Vec::new();
+        while server.wants_write() {
+            server.write_tls(&mut buffer).unwrap();
+        }
+        client.read_tls(&mut buffer.as_slice()).unwrap();
+        client.process_new_packets().ok()?;
+    }
+
+    client
+        .peer_certificates()
+        .and_then(|chain| chain.first())
+        .map(|cert| cert.to_vec())
+}
+
+#[test]
+fn picks_exact_name_then_longest_suffix_then_fallback() {
+    let sandbox = Sandbox::new("tls-resolve");
+    let suffix = sandbox.pair("suffix", &["*.example.test"]);
+    let deep = sandbox.pair("deep", &["*.api.example.test"]);
+    let exact = sandbox.pair("exact", &["www.example.test"]);
+    let fallback = sandbox.pair("fallback", &["localhost"]);
+
+    // -> Added in the opposite order of their rank
+    let certificates = Certificates::new()
+        .add(patterns(&["*.example.test"]), &suffix.cert, &suffix.key)
+        .unwrap()
+        .add(patterns(&["*.api.example.test"]), &deep.cert, &deep.key)
+        .unwrap()
+        .add(patterns(&["www.example.test"]), &exact.cert, &exact.key)
+        .unwrap()
+        .add(Vec::new(), &fallback.cert, &fallback.key)
+        .unwrap();
+    let certificates = Arc::new(certificates);
+
+    assert_eq!(
+        served(&certificates, Some("www.example.test")),
+        Some(exact.der)
+    );
+    assert_eq!(
+        served(&certificates, Some("shop.example.test")),
+        Some(suffix.der)
+    );
+    assert_eq!(
+        served(&certificates, Some("v1.api.example.test")),
+        Some(deep.der)
+    );
+    assert_eq!(
+        served(&certificates, Some("other.test")),
+        Some(fallback.der.clone())
+    );
+    assert_eq!(served(&certificates, None), Some(fallback.der));
+}
+
+#[test]
+fn fails_handshake_for_unknown_name_without_fallback() {
+    let sandbox = Sandbox::new("tls-nofallback");
+    let exact = sandbox.pair("exact", &["www.example.test"]);
+
+    let certificates = Certificates::new()
+        .add(patterns(&["www.example.test"]), &exact.cert, &exact.key)
+        .unwrap();
+    let certificates = Arc::new(certificates);
+
+    assert_eq!(
+        served(&certificates, Some("www.example.test")),
+        Some(exact.der)
+    );
+    assert_eq!(served(&certificates, Some("other.test")), None);
+    assert_eq!(served(&certificates, None), None);
+}
+
+#[test]
+fn refuses_key_that_does_not_fit_the_certificate() {
+    let sandbox = Sandbox::new("tls-mismatch");
+    let first = sandbox.pair("first", &["a.test"]);
+    let second = sandbox.pair("second", &["b.test"]);
+
+    assert!(
+        Certificates::new()
+            .add(Vec::new(), &first.cert, &second.key)
+            .is_err()
+    );
+}
+
+#[test]
+fn reload_keeps_old_pair_until_new_one_is_complete() {
+    let sandbox = Sandbox::new("tls-reload");
+    let live = sandbox.pair("live", &["www.example.test"]);
+    let next = sandbox.pair("next", &["www.example.test"]);
+
+    let certificates = Certificates::new()
+        .add(Vec::new(), &live.cert, &live.key)
+        .unwrap();
+    let certificates = Arc::new(certificates);
+    assert_eq!(
+        served(&certificates, Some("www.example.test")),
+        Some(live.der.clone())
+    );
+
+    // -> Only the certificate replaced so far, it doesn't fit the old key
+    replace(&next.cert, &live.cert, 10);
+    certificates.reload();
+    assert_eq!(
+        served(&certificates, Some("www.example.test")),
+        Some(live.der)
+    );
+
+    replace(&next.key, &live.key, 20);
+    certificates.reload();
+    assert_eq!(
+        served(&certificates, Some("www.example.test")),
+        Some(next.der)
+    );
+}
+
+#[test]
+fn parses_tls_versions() {
+    assert_eq!("1.2".parse::<TlsVersion>(), Ok(TlsVersion::Tls12));
+    assert_eq!(" 1.3 ".parse::<TlsVersion>(), Ok(TlsVersion::Tls13));
+    assert_eq!("TLSv1.2".parse::<TlsVersion>(), Ok(TlsVersion::Tls12));
+    assert_eq!("tls1.3".parse::<TlsVersion>(), Ok(TlsVersion::Tls13));
+    assert_eq!("1.1".parse::<TlsVersion>(), Err(String::from("1.1")));
+    assert_eq!("ssl3".parse::<TlsVersion>(), Err(String::from("ssl3")));
+    assert_eq!(TlsVersion::Tls13.to_string(), "1.3");
+}